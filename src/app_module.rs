@@ -1,6 +1,8 @@
+use crate::config::Config;
 use crate::ext::MonitorExt;
 use crate::widgets::{
-    DateTime, Focused, HyprlandMessage, Language, LanguageInit, PowerMenu, SysTray, WorkspacesModel,
+    Audio, DateTime, Focused, HyprlandMessage, IdleInhibit, Language, LockKeys, Mpris, PowerMenu,
+    SysTray, Timer, WorkspacesModel,
 };
 use crate::workers::HyprlandHandler;
 use gtk::gdk;
@@ -29,6 +31,11 @@ pub struct AppModel {
     sys_tray: AsyncController<SysTray>,
     datetime: AsyncController<DateTime>,
     language: AsyncController<Language>,
+    lock_keys: AsyncController<LockKeys>,
+    mpris: AsyncController<Mpris>,
+    audio: AsyncController<Audio>,
+    timer: AsyncController<Timer>,
+    idle_inhibit: Controller<IdleInhibit>,
 }
 
 #[derive(Debug)]
@@ -87,8 +94,13 @@ impl SimpleAsyncComponent for AppModel {
                     set_orientation: gtk::Orientation::Horizontal,
                     set_spacing: 8,
 
+                    model.mpris.widget(),
+                    model.audio.widget(),
+                    model.lock_keys.widget(),
                     model.language.widget(),
                     model.sys_tray.widget(),
+                    model.idle_inhibit.widget(),
+                    model.timer.widget(),
                     model.datetime.widget(),
                     model.power_menu.widget(),
                 }
@@ -101,16 +113,18 @@ impl SimpleAsyncComponent for AppModel {
         root: Self::Root,
         _sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
+        let config = Config::load();
         let workspaces = WorkspacesModel::builder().launch(()).detach();
         let focused = Focused::builder().launch(()).detach();
-        let power_menu = PowerMenu::builder().launch(()).detach();
-        let sys_tray = SysTray::builder().launch(()).detach();
-        let datetime = DateTime::builder().launch(()).detach();
-        let language = Language::builder()
-            .launch(LanguageInit::new(
-                "turing-gaming-keyboard-turing-gaming-keyboard",
-            ))
-            .detach();
+        let power_menu = PowerMenu::builder().launch(config.power_menu).detach();
+        let sys_tray = SysTray::builder().launch(config.tray).detach();
+        let datetime = DateTime::builder().launch(config.datetime).detach();
+        let language = Language::builder().launch(config.language).detach();
+        let lock_keys = LockKeys::builder().launch(config.lock_keys).detach();
+        let mpris = Mpris::builder().launch(config.mpris).detach();
+        let audio = Audio::builder().launch(config.audio).detach();
+        let timer = Timer::builder().launch(config.timer).detach();
+        let idle_inhibit = IdleInhibit::builder().launch(config.idle_inhibit).detach();
 
         let language_sender = language.sender().clone();
         let workspaces_sender = workspaces.sender().clone();
@@ -122,11 +136,15 @@ impl SimpleAsyncComponent for AppModel {
                 let message_clone = message.clone();
                 match message_clone {
                     HyprlandMessage::ActiveWindow { .. } => {
+                        if let Err(e) = language_sender.send(message_clone.clone()) {
+                            error!("Error sending message to Language: {:?}", e);
+                        }
                         if let Err(e) = focused_sender.send(message_clone) {
                             error!("Error sending message to Focused: {:?}", e);
                         }
                     }
-                    HyprlandMessage::SwitchKeyboardLayout { .. } => {
+                    HyprlandMessage::SwitchKeyboardLayout { .. }
+                    | HyprlandMessage::CloseWindow { .. } => {
                         if let Err(e) = language_sender.send(message_clone) {
                             error!("Error sending message to Language: {:?}", e);
                         }
@@ -153,6 +171,11 @@ impl SimpleAsyncComponent for AppModel {
             sys_tray,
             datetime,
             language,
+            lock_keys,
+            mpris,
+            audio,
+            timer,
+            idle_inhibit,
         };
 
         let widgets = view_output!();
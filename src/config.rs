@@ -0,0 +1,68 @@
+use crate::widgets::{
+    AudioInit, DateTimeInit, IdleInhibitInit, LanguageInit, LockKeysInit, MprisInit, PowerMenuInit,
+    SysTrayInit, TimerInit,
+};
+use gtk::glib;
+use log::{error, info};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Bar configuration, read from `$XDG_CONFIG_HOME/rgb/config.json`.
+/// Every section is optional and falls back to defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub audio: AudioInit,
+    pub datetime: DateTimeInit,
+    pub idle_inhibit: IdleInhibitInit,
+    pub language: LanguageInit,
+    pub lock_keys: LockKeysInit,
+    pub mpris: MprisInit,
+    pub power_menu: PowerMenuInit,
+    pub tray: SysTrayInit,
+    pub timer: TimerInit,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            audio: AudioInit::default(),
+            datetime: DateTimeInit::default(),
+            idle_inhibit: IdleInhibitInit::default(),
+            language: LanguageInit::new("turing-gaming-keyboard-turing-gaming-keyboard"),
+            lock_keys: LockKeysInit::default(),
+            mpris: MprisInit::default(),
+            power_menu: PowerMenuInit::default(),
+            tray: SysTrayInit::default(),
+            timer: TimerInit::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        glib::user_config_dir().join("rgb").join("config.json")
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("No config at {}, using defaults", path.display());
+                return Self::default();
+            }
+            Err(err) => {
+                error!("Error reading config {}: {err}", path.display());
+                return Self::default();
+            }
+        };
+        match serde_json::from_str(&text) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("Error parsing config {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+}
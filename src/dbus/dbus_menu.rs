@@ -0,0 +1,42 @@
+use super::split_address;
+use std::collections::HashMap;
+use zbus::dbus_proxy;
+use zbus::zvariant::OwnedValue;
+
+/// Properties changed per item id
+pub type UpdatedProps = Vec<(i32, HashMap<String, OwnedValue>)>;
+/// Properties reset to their defaults per item id
+pub type RemovedProps = Vec<(i32, Vec<String>)>;
+
+/// Menu exported by a tray item
+#[dbus_proxy(interface = "com.canonical.dbusmenu", gen_blocking = false)]
+pub trait DBusMenu {
+    /// Called before the menu or a submenu is shown, returns whether the layout needs to be fetched again
+    fn about_to_show(&self, id: i32) -> zbus::Result<bool>;
+
+    #[dbus_proxy(signal)]
+    fn items_properties_updated(
+        &self,
+        updated_props: UpdatedProps,
+        removed_props: RemovedProps,
+    ) -> zbus::Result<()>;
+
+    #[dbus_proxy(signal)]
+    fn layout_updated(&self, revision: u32, parent: i32) -> zbus::Result<()>;
+}
+
+impl DBusMenuProxy<'static> {
+    /// Menu of the tray item at the address, the path comes from its `Menu` property
+    pub async fn for_item(
+        connection: &zbus::Connection,
+        address: &str,
+        menu_path: &str,
+    ) -> zbus::Result<Self> {
+        let (destination, _) = split_address(address);
+        Self::builder(connection)
+            .destination(destination.to_string())?
+            .path(menu_path.to_string())?
+            .build()
+            .await
+    }
+}
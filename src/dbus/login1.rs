@@ -0,0 +1,48 @@
+use zbus::dbus_proxy;
+
+/// systemd-logind manager, see `org.freedesktop.login1(5)`
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1",
+    gen_blocking = false
+)]
+pub trait Login1Manager {
+    /// `interactive` lets polkit ask the user for a password
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+    fn hybrid_sleep(&self, interactive: bool) -> zbus::Result<()>;
+
+    /// Returns "yes", "no", "challenge" or "na"
+    fn can_power_off(&self) -> zbus::Result<String>;
+    fn can_reboot(&self) -> zbus::Result<String>;
+    fn can_suspend(&self) -> zbus::Result<String>;
+    fn can_hibernate(&self) -> zbus::Result<String>;
+    fn can_hybrid_sleep(&self) -> zbus::Result<String>;
+
+    /// Lock is held until the returned descriptor is closed
+    fn inhibit(
+        &self,
+        what: &str,
+        who: &str,
+        why: &str,
+        mode: &str,
+    ) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+    /// Sent with `true` right before suspend and with `false` after resume
+    #[dbus_proxy(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Session the bar runs in, `auto` is resolved by logind to the caller's session
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto",
+    gen_blocking = false
+)]
+pub trait Login1Session {
+    fn lock(&self) -> zbus::Result<()>;
+}
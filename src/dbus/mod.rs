@@ -0,0 +1,22 @@
+mod dbus_menu;
+mod login1;
+mod mpris;
+mod status_notifier_item;
+
+pub use dbus_menu::*;
+pub use login1::*;
+pub use mpris::*;
+pub use status_notifier_item::*;
+
+use std::sync::OnceLock;
+
+static SESSION: OnceLock<zbus::Connection> = OnceLock::new();
+
+/// Shared session bus connection, opened on first use
+pub async fn session() -> zbus::Result<zbus::Connection> {
+    if let Some(connection) = SESSION.get() {
+        return Ok(connection.clone());
+    }
+    let connection = zbus::Connection::session().await?;
+    Ok(SESSION.get_or_init(|| connection).clone())
+}
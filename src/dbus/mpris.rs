@@ -0,0 +1,47 @@
+use zbus::dbus_proxy;
+use zbus::zvariant::ObjectPath;
+
+/// Bus names of media players start with it
+pub const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+pub const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+pub const MPRIS_PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Media player, see the MPRIS D-Bus interface specification
+#[dbus_proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2",
+    gen_blocking = false
+)]
+pub trait MediaPlayer2 {
+    /// Brings the player window to the front
+    fn raise(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn identity(&self) -> zbus::Result<String>;
+}
+
+/// Playback controls of a media player, times are in microseconds
+#[dbus_proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2",
+    gen_blocking = false
+)]
+pub trait Player {
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+    fn play_pause(&self) -> zbus::Result<()>;
+    /// Relative to the current position
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+    /// Ignored by the player if the track id is not the current one
+    fn set_position(&self, track_id: &ObjectPath<'_>, position: i64) -> zbus::Result<()>;
+
+    /// Position jumped, it isn't signalled while playing normally
+    #[dbus_proxy(signal)]
+    fn seeked(&self, position: i64) -> zbus::Result<()>;
+
+    /// From 0.0 to 1.0
+    #[dbus_proxy(property)]
+    fn volume(&self) -> zbus::Result<f64>;
+    #[dbus_proxy(property)]
+    fn set_volume(&self, value: f64) -> zbus::Result<()>;
+}
@@ -0,0 +1,31 @@
+use zbus::dbus_proxy;
+
+/// Tray item, service and path come from the watcher
+#[dbus_proxy(interface = "org.kde.StatusNotifierItem", gen_blocking = false)]
+pub trait StatusNotifierItem {
+    /// Coordinates are where a window may be shown, Wayland clients pass zeros
+    fn activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+    fn secondary_activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+    /// Orientation is "vertical" or "horizontal"
+    fn scroll(&self, delta: i32, orientation: &str) -> zbus::Result<()>;
+}
+
+impl StatusNotifierItemProxy<'static> {
+    pub async fn for_address(connection: &zbus::Connection, address: &str) -> zbus::Result<Self> {
+        let (destination, path) = split_address(address);
+        Self::builder(connection)
+            .destination(destination.to_string())?
+            .path(path.to_string())?
+            .build()
+            .await
+    }
+}
+
+/// Tray address is the bus name followed by object path,
+/// items registered with a bare bus name use the default path
+pub fn split_address(address: &str) -> (&str, &str) {
+    match address.find('/') {
+        Some(index) => (&address[..index], &address[index..]),
+        None => (address, "/StatusNotifierItem"),
+    }
+}
@@ -1,4 +1,7 @@
+use xkb_data::{KeyboardLayout, KeyboardVariant};
+
 pub trait KeyboardLayoutEx {
+    #[allow(dead_code)]
     fn find(display_name: &str) -> Option<xkb_data::KeyboardLayout> {
         if let Ok(layouts) = xkb_data::keyboard_layouts() {
             let x = layouts.layout_list.layout;
@@ -13,7 +16,55 @@ pub trait KeyboardLayoutEx {
         }
     }
 
+    fn find_by_name(name: &str) -> Option<xkb_data::KeyboardLayout> {
+        let layouts = xkb_data::keyboard_layouts().ok()?;
+        layouts
+            .layout_list
+            .layout
+            .iter()
+            .find(|&it| it.name() == name)
+            .cloned()
+    }
+
+    /// Finds layout by description of either layout itself or one of its variants,
+    /// e.g. "English (US, intl., with dead keys)"
+    fn find_variant(display_name: &str) -> Option<(KeyboardLayout, Option<KeyboardVariant>)> {
+        let layouts = xkb_data::keyboard_layouts().ok()?;
+        layouts.layout_list.layout.iter().find_map(|it| {
+            if it.description() == display_name {
+                return Some((it.clone(), None));
+            }
+            let variant = it
+                .variants()?
+                .iter()
+                .find(|&variant| variant.description() == display_name)?;
+            Some((it.clone(), Some(variant.clone())))
+        })
+    }
+
+    /// Finds layout and variant by their xkb names, e.g. "us" and "intl"
+    fn find_by_names(
+        name: &str,
+        variant: &str,
+    ) -> Option<(KeyboardLayout, Option<KeyboardVariant>)> {
+        let layout = Self::find_by_name(name)?;
+        if variant.is_empty() {
+            return Some((layout, None));
+        }
+        let variant = layout
+            .variants()
+            .and_then(|it| it.iter().find(|&it| it.name() == variant))
+            .cloned();
+        Some((layout, variant))
+    }
+
     fn localized(&self) -> Option<String>;
+
+    /// Short label with variant appended, e.g. "US-INTL"
+    fn short_label(&self, variant: Option<&KeyboardVariant>) -> Option<String>;
+
+    /// Emoji flag for layouts named after a country, e.g. "ru"
+    fn flag(&self) -> Option<String>;
 }
 
 impl KeyboardLayoutEx for xkb_data::KeyboardLayout {
@@ -24,6 +75,26 @@ impl KeyboardLayoutEx for xkb_data::KeyboardLayout {
             Some(region) => region.to_string(),
         })
     }
+
+    fn short_label(&self, variant: Option<&KeyboardVariant>) -> Option<String> {
+        let localized = self.localized()?;
+        let label = match variant {
+            None => localized,
+            Some(variant) => format!("{localized}-{}", variant.name()),
+        };
+        Some(label.to_uppercase())
+    }
+
+    fn flag(&self) -> Option<String> {
+        let name = self.name();
+        if name.len() != 2 || !name.chars().all(|it| it.is_ascii_alphabetic()) {
+            return None;
+        }
+        name.to_ascii_uppercase()
+            .chars()
+            .map(|it| char::from_u32(0x1F1E6 + (it as u32 - 'A' as u32)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -62,4 +133,65 @@ mod tests {
         assert_ne!(localized, None);
         Ok(())
     }
+
+    #[test]
+    fn test_find_layout_by_variant_description() -> Result<(), &'static str> {
+        init();
+        let (layout, variant) =
+            xkb_data::KeyboardLayout::find_variant("English (US, intl., with dead keys)")
+                .expect("Wasn't found!");
+        assert_eq!(layout.name(), "us");
+        assert_eq!(variant.expect("Variant wasn't found!").name(), "intl");
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_layout_without_variant() -> Result<(), &'static str> {
+        init();
+        let (layout, variant) =
+            xkb_data::KeyboardLayout::find_variant("Russian").expect("Wasn't found!");
+        assert_eq!(layout.name(), "ru");
+        assert!(variant.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_unknown_layout() -> Result<(), &'static str> {
+        init();
+        assert!(xkb_data::KeyboardLayout::find_variant("Not a layout").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_label() -> Result<(), &'static str> {
+        init();
+        let (layout, variant) =
+            xkb_data::KeyboardLayout::find_by_names("us", "intl").expect("Wasn't found!");
+        assert_eq!(
+            layout.short_label(variant.as_ref()).as_deref(),
+            Some("US-INTL")
+        );
+        let (layout, variant) =
+            xkb_data::KeyboardLayout::find_by_names("ru", "").expect("Wasn't found!");
+        assert_eq!(layout.short_label(variant.as_ref()).as_deref(), Some("RU"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_flag() -> Result<(), &'static str> {
+        init();
+        let result = xkb_data::KeyboardLayout::find_by_name("ru").expect("Wasn't found!");
+        assert_eq!(result.flag().as_deref(), Some("🇷🇺"));
+        let result = xkb_data::KeyboardLayout::find_by_name("ara").expect("Wasn't found!");
+        assert_eq!(result.flag(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_layout_by_name() -> Result<(), &'static str> {
+        init();
+        let result = xkb_data::KeyboardLayout::find_by_name("ru").expect("Wasn't found!");
+        assert_eq!(result.description(), "Russian");
+        Ok(())
+    }
 }
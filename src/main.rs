@@ -8,8 +8,11 @@ use relm4::once_cell::sync::Lazy;
 use relm4::RelmApp;
 
 mod app_module;
+mod config;
+mod dbus;
 mod ext;
 mod macros;
+mod wayland;
 mod widgets;
 mod workers;
 
@@ -0,0 +1,106 @@
+use gtk::gdk;
+use gtk::prelude::Cast;
+use log::{debug, error};
+use smithay_client_toolkit::reexports::client::globals::{registry_queue_init, GlobalListContents};
+use smithay_client_toolkit::reexports::client::protocol::wl_registry::{self, WlRegistry};
+use smithay_client_toolkit::reexports::client::{
+    delegate_noop, Connection, Dispatch, EventQueue, QueueHandle,
+};
+use smithay_client_toolkit::reexports::protocols::wp::idle_inhibit::zv1::client::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1;
+use smithay_client_toolkit::reexports::protocols::wp::idle_inhibit::zv1::client::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1;
+
+/// `zwp_idle_inhibit_manager_v1` bound once, creates inhibitors on demand
+pub struct IdleInhibitManager {
+    connection: Connection,
+    queue: EventQueue<InhibitorState>,
+    manager: ZwpIdleInhibitManagerV1,
+}
+
+/// Holds `zwp_idle_inhibitor_v1` on a GTK surface, released on drop.
+/// Compositor honors it only while the surface is visible, so the bar's own surface is used.
+pub struct IdleInhibitor {
+    connection: Connection,
+    inhibitor: ZwpIdleInhibitorV1,
+}
+
+struct InhibitorState;
+
+impl IdleInhibitManager {
+    pub fn new(display: &gdk::Display) -> Option<Self> {
+        let Some(display) = display.downcast_ref::<gdk4_wayland::WaylandDisplay>() else {
+            error!("Idle inhibitor needs a Wayland display");
+            return None;
+        };
+        let backend = display.wl_display()?.backend().upgrade()?;
+        // Shares GTK's connection, objects created by GTK are valid here
+        let connection = Connection::from_backend(backend);
+
+        let (globals, queue) = registry_queue_init::<InhibitorState>(&connection)
+            .inspect_err(|err| error!("Error reading Wayland globals: {err}"))
+            .ok()?;
+        let manager = globals
+            .bind(&queue.handle(), 1..=1, ())
+            .inspect_err(|err| error!("Compositor has no idle inhibit manager: {err}"))
+            .ok()?;
+
+        Some(Self {
+            connection,
+            queue,
+            manager,
+        })
+    }
+
+    pub fn inhibit(&mut self, surface: &gdk::Surface) -> Option<IdleInhibitor> {
+        let wl_surface = surface
+            .downcast_ref::<gdk4_wayland::WaylandSurface>()?
+            .wl_surface()?;
+        let inhibitor = self
+            .manager
+            .create_inhibitor(&wl_surface, &self.queue.handle(), ());
+        if let Err(err) = self.queue.roundtrip(&mut InhibitorState) {
+            error!("Error creating idle inhibitor: {err}");
+            inhibitor.destroy();
+            return None;
+        }
+        debug!("Idle inhibitor created");
+
+        Some(IdleInhibitor {
+            connection: self.connection.clone(),
+            inhibitor,
+        })
+    }
+}
+
+impl Drop for IdleInhibitManager {
+    fn drop(&mut self) {
+        self.manager.destroy();
+        if let Err(err) = self.connection.flush() {
+            error!("Error releasing idle inhibit manager: {err}");
+        }
+    }
+}
+
+impl Drop for IdleInhibitor {
+    fn drop(&mut self) {
+        self.inhibitor.destroy();
+        if let Err(err) = self.connection.flush() {
+            error!("Error releasing idle inhibitor: {err}");
+        }
+        debug!("Idle inhibitor released");
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for InhibitorState {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(InhibitorState: ignore ZwpIdleInhibitManagerV1);
+delegate_noop!(InhibitorState: ignore ZwpIdleInhibitorV1);
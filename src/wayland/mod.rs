@@ -0,0 +1,3 @@
+mod idle_inhibitor;
+
+pub use idle_inhibitor::*;
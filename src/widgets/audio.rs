@@ -0,0 +1,378 @@
+use crate::ext::ParentFor;
+use crate::widgets::audio_device::{AudioDevice, AudioDeviceMessage};
+use crate::widgets::audio_stream::{AudioStream, AudioStreamMessage, StreamControl};
+use crate::workers::{Device, DeviceKind, PulseCommand, PulseHandle, PulseState};
+use gtk::glib::Propagation;
+use gtk::prelude::{BoxExt, ButtonExt, OrientableExt, PopoverExt, RangeExt, WidgetExt};
+use gtk::EventControllerScrollFlags;
+use relm4::component::AsyncComponentParts;
+use relm4::factory::FactoryVecDeque;
+use relm4::prelude::SimpleAsyncComponent;
+use relm4::{AsyncComponentSender, RelmWidgetExt};
+use serde::Deserialize;
+
+/// Default output volume and mute, with the default input's mute next to it
+pub struct Audio {
+    pulse: PulseHandle,
+    state: PulseState,
+    outputs: FactoryVecDeque<AudioDevice>,
+    inputs: FactoryVecDeque<AudioDevice>,
+    /// Mixer of applications playing sound
+    streams: FactoryVecDeque<AudioStream>,
+    step: f64,
+    max_volume: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AudioInit {
+    /// Volume change per scroll step, 1.0 is 100%
+    pub step: f64,
+    /// Scrolling and sliders stop here, above 1.0 amplifies
+    pub max_volume: f64,
+}
+
+impl Default for AudioInit {
+    fn default() -> Self {
+        Self {
+            step: 0.05,
+            max_volume: 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AudioMessage {
+    State(PulseState),
+    Scroll(f64),
+    ToggleMute(DeviceKind),
+    SetVolume(DeviceKind, f64),
+    SetDefault(DeviceKind, String),
+    Stream(u32, StreamControl),
+}
+
+#[relm4::component(pub async)]
+impl SimpleAsyncComponent for Audio {
+    type Init = AudioInit;
+    type Input = AudioMessage;
+    type Output = ();
+
+    view! {
+        #[root]
+        gtk::Box {
+            add_css_class: "module",
+            add_css_class: "audio",
+            set_spacing: 4,
+            #[watch]
+            set_visible: model.state.connected,
+
+            set_parent_for: popover = &gtk::Popover {
+                add_css_class: "menu",
+
+                #[wrap(Some)]
+                set_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+
+                    gtk::Label {
+                        add_css_class: "audio__heading",
+                        set_halign: gtk::Align::Start,
+                        set_label: "Output",
+                    },
+
+                    gtk::Scale {
+                        set_draw_value: false,
+                        #[watch]
+                        set_range: (0f64, model.slider_max(DeviceKind::Sink)),
+                        #[watch]
+                        set_sensitive: model.sink().is_some(),
+                        #[watch]
+                        set_value: model.sink().map(|it| it.volume).unwrap_or(0f64),
+                        connect_change_value[sender] => move |_, _, value| {
+                            sender.input(AudioMessage::SetVolume(DeviceKind::Sink, value));
+                            Propagation::Proceed
+                        },
+                    },
+
+                    append: model.outputs.widget(),
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 4,
+                        #[watch]
+                        set_visible: !model.state.sources.is_empty(),
+
+                        gtk::Label {
+                            add_css_class: "audio__heading",
+                            set_halign: gtk::Align::Start,
+                            set_label: "Input",
+                        },
+
+                        gtk::Scale {
+                            set_draw_value: false,
+                            #[watch]
+                            set_range: (0f64, model.slider_max(DeviceKind::Source)),
+                            #[watch]
+                            set_sensitive: model.source().is_some(),
+                            #[watch]
+                            set_value: model.source().map(|it| it.volume).unwrap_or(0f64),
+                            connect_change_value[sender] => move |_, _, value| {
+                                sender.input(AudioMessage::SetVolume(DeviceKind::Source, value));
+                                Propagation::Proceed
+                            },
+                        },
+
+                        append: model.inputs.widget(),
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 4,
+                        #[watch]
+                        set_visible: !model.state.streams.is_empty(),
+
+                        gtk::Label {
+                            add_css_class: "audio__heading",
+                            set_halign: gtk::Align::Start,
+                            set_label: "Applications",
+                        },
+
+                        append: model.streams.widget(),
+                    },
+                },
+            },
+
+            add_controller = gtk::EventControllerScroll::new(EventControllerScrollFlags::VERTICAL) {
+                connect_scroll[sender] => move |_, _, y| {
+                    sender.input(AudioMessage::Scroll(y));
+                    Propagation::Stop
+                }
+            },
+
+            gtk::Button {
+                add_css_class: "audio__output",
+                #[watch]
+                set_class_active: ("muted", model.sink().is_some_and(|it| it.mute)),
+                #[watch]
+                set_tooltip_text: model.sink().map(|it| it.description.as_str()),
+                connect_clicked => AudioMessage::ToggleMute(DeviceKind::Sink),
+
+                add_controller = gtk::GestureClick::new() {
+                    set_button: gtk::gdk::BUTTON_SECONDARY,
+                    connect_pressed[popover] => move |_, _, _, _| {
+                        popover.popup();
+                    }
+                },
+
+                #[wrap(Some)]
+                set_child = &gtk::Box {
+                    set_spacing: 4,
+
+                    gtk::Image {
+                        #[watch]
+                        set_icon_name: Some(volume_icon(model.sink())),
+                    },
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &model.sink().map(percent).unwrap_or_default(),
+                    },
+                },
+            },
+
+            gtk::Button {
+                add_css_class: "audio__input",
+                #[watch]
+                set_visible: model.source().is_some(),
+                #[watch]
+                set_class_active: ("muted", model.source().is_some_and(|it| it.mute)),
+                #[watch]
+                set_icon_name: microphone_icon(model.source()),
+                #[watch]
+                set_tooltip_text: model.source().map(|it| it.description.as_str()),
+                connect_clicked => AudioMessage::ToggleMute(DeviceKind::Source),
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        let pulse = {
+            let sender = sender.clone();
+            PulseHandle::start(None, move |state| sender.input(AudioMessage::State(state)))
+        };
+        let model = Self {
+            pulse,
+            state: PulseState::default(),
+            outputs: FactoryVecDeque::builder()
+                .launch(gtk::Box::new(gtk::Orientation::Vertical, 0))
+                .forward(sender.input_sender(), |(kind, name)| {
+                    AudioMessage::SetDefault(kind, name)
+                }),
+            inputs: FactoryVecDeque::builder()
+                .launch(gtk::Box::new(gtk::Orientation::Vertical, 0))
+                .forward(sender.input_sender(), |(kind, name)| {
+                    AudioMessage::SetDefault(kind, name)
+                }),
+            streams: FactoryVecDeque::builder()
+                .launch(gtk::Box::new(gtk::Orientation::Vertical, 4))
+                .forward(sender.input_sender(), |(index, control)| {
+                    AudioMessage::Stream(index, control)
+                }),
+            step: init.step,
+            max_volume: init.max_volume,
+        };
+        let widgets = view_output!();
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
+        match message {
+            AudioMessage::State(state) => {
+                sync_devices(&mut self.outputs, DeviceKind::Sink, &state);
+                sync_devices(&mut self.inputs, DeviceKind::Source, &state);
+                self.sync_streams(&state);
+                self.state = state;
+            }
+            AudioMessage::Scroll(delta) => {
+                // Scrolling up is louder
+                if let Some(sink) = self.sink() {
+                    let volume = sink.volume - delta * self.step;
+                    self.set_volume(DeviceKind::Sink, volume);
+                }
+            }
+            AudioMessage::ToggleMute(kind) => {
+                let Some(device) = self.state.default_device(kind) else {
+                    return;
+                };
+                let command = PulseCommand::SetMute(kind, device.name.clone(), !device.mute);
+                self.pulse.send(command);
+            }
+            AudioMessage::SetVolume(kind, volume) => self.set_volume(kind, volume),
+            AudioMessage::SetDefault(kind, name) => {
+                self.pulse.send(PulseCommand::SetDefault(kind, name));
+            }
+            AudioMessage::Stream(index, StreamControl::Volume(volume)) => {
+                let Some(stream) = self.state.streams.iter_mut().find(|it| it.index == index)
+                else {
+                    return;
+                };
+                // Like devices, a stream above the limit isn't pulled down
+                let volume = volume.clamp(0f64, self.max_volume.max(stream.volume));
+                stream.volume = volume;
+                self.pulse
+                    .send(PulseCommand::SetStreamVolume(index, volume));
+            }
+            AudioMessage::Stream(index, StreamControl::Mute(mute)) => {
+                self.pulse.send(PulseCommand::SetStreamMute(index, mute));
+            }
+        }
+    }
+}
+
+impl Audio {
+    fn sink(&self) -> Option<&Device> {
+        self.state.default_device(DeviceKind::Sink)
+    }
+
+    fn source(&self) -> Option<&Device> {
+        self.state.default_device(DeviceKind::Source)
+    }
+
+    /// Volume raised above the limit elsewhere isn't cut off by the slider
+    fn slider_max(&self, kind: DeviceKind) -> f64 {
+        let volume = self.state.default_device(kind).map_or(0f64, |it| it.volume);
+        self.max_volume.max(volume)
+    }
+
+    fn set_volume(&mut self, kind: DeviceKind, volume: f64) {
+        let Some(device) = self.state.default_device(kind) else {
+            return;
+        };
+        // Volume raised above the limit elsewhere isn't pulled down by scrolling up
+        let volume = volume.clamp(0f64, self.max_volume.max(device.volume));
+        let name = device.name.clone();
+        // Shown right away, so fast scrolling doesn't start from a stale value
+        let devices = match kind {
+            DeviceKind::Sink => &mut self.state.sinks,
+            DeviceKind::Source => &mut self.state.sources,
+        };
+        if let Some(device) = devices.iter_mut().find(|it| it.name == name) {
+            device.volume = volume;
+        }
+        self.pulse.send(PulseCommand::SetVolume(kind, name, volume));
+    }
+
+    /// Streams come and go one by one, so rows are kept to not interrupt dragging
+    fn sync_streams(&mut self, state: &PulseState) {
+        let mut guard = self.streams.guard();
+        let mut row = 0;
+        while let Some(index) = guard.get(row).map(|it| it.index) {
+            match state.streams.iter().find(|it| it.index == index) {
+                Some(stream) => {
+                    guard.send(row, AudioStreamMessage::Update(stream.clone()));
+                    row += 1;
+                }
+                None => {
+                    _ = guard.remove(row);
+                }
+            }
+        }
+        for stream in &state.streams {
+            if !guard.iter().any(|it| it.index == stream.index) {
+                guard.push_back((stream.clone(), self.max_volume));
+            }
+        }
+    }
+}
+
+/// Rebuilds the list when devices come or go, otherwise just updates the rows
+fn sync_devices(rows: &mut FactoryVecDeque<AudioDevice>, kind: DeviceKind, state: &PulseState) {
+    let devices = state.devices(kind);
+    let default = state.default_name(kind);
+    let is_default = |device: &Device| Some(device.name.as_str()) == default;
+    let mut guard = rows.guard();
+    let same = guard.len() == devices.len()
+        && guard
+            .iter()
+            .zip(devices)
+            .all(|(row, it)| row.name == it.name);
+    if same {
+        for (index, device) in devices.iter().enumerate() {
+            guard.send(
+                index,
+                AudioDeviceMessage::Update(device.clone(), is_default(device)),
+            );
+        }
+    } else {
+        guard.clear();
+        for device in devices {
+            guard.push_back((kind, device.clone(), is_default(device)));
+        }
+    }
+}
+
+fn volume_icon(device: Option<&Device>) -> &'static str {
+    match device {
+        Some(device) if !device.mute && device.volume > 0f64 => match device.volume {
+            volume if volume < 0.34 => "audio-volume-low-symbolic",
+            volume if volume < 0.67 => "audio-volume-medium-symbolic",
+            _ => "audio-volume-high-symbolic",
+        },
+        _ => "audio-volume-muted-symbolic",
+    }
+}
+
+fn microphone_icon(device: Option<&Device>) -> &'static str {
+    match device {
+        Some(device) if !device.mute => "audio-input-microphone-symbolic",
+        _ => "microphone-disabled-symbolic",
+    }
+}
+
+fn percent(device: &Device) -> String {
+    format!("{:.0}%", device.volume * 100f64)
+}
@@ -0,0 +1,77 @@
+use crate::workers::{Device, DeviceKind};
+use gtk::prelude::{BoxExt, ButtonExt, WidgetExt};
+use relm4::factory::FactoryComponent;
+use relm4::{FactorySender, RelmWidgetExt};
+
+/// Output or input device in the popover, clicking makes it the default
+#[derive(Debug)]
+pub struct AudioDevice {
+    pub name: String,
+    kind: DeviceKind,
+    description: String,
+    default: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum AudioDeviceMessage {
+    Update(Device, bool),
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for AudioDevice {
+    type Init = (DeviceKind, Device, bool);
+    type Input = AudioDeviceMessage;
+    type Output = (DeviceKind, String);
+    type ParentWidget = gtk::Box;
+    type CommandOutput = ();
+
+    view! {
+        #[root]
+        gtk::Button {
+            add_css_class: "audio__device",
+            add_css_class: "flat",
+            #[watch]
+            set_class_active: ("default", self.default),
+            connect_clicked[sender, kind = self.kind, name = self.name.clone()] => move |_| {
+                _ = sender.output((kind, name.clone()));
+            },
+
+            #[wrap(Some)]
+            set_child = &gtk::Box {
+                set_spacing: 8,
+
+                gtk::Image {
+                    set_icon_name: Some("object-select-symbolic"),
+                    #[watch]
+                    set_opacity: if self.default { 1f64 } else { 0f64 },
+                },
+
+                gtk::Label {
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    set_max_width_chars: 40,
+                    #[watch]
+                    set_label: &self.description,
+                    #[watch]
+                    set_tooltip_text: Some(&self.name),
+                },
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
+        let (kind, device, default) = init;
+        Self {
+            name: device.name,
+            kind,
+            description: device.description,
+            default,
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: FactorySender<Self>) {
+        let AudioDeviceMessage::Update(device, default) = message;
+        self.description = device.description;
+        self.default = default;
+    }
+}
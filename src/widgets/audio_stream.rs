@@ -0,0 +1,132 @@
+use crate::workers::Stream;
+use gtk::gdk;
+use gtk::glib::Propagation;
+use gtk::prelude::{BoxExt, ButtonExt, OrientableExt, RangeExt, ToggleButtonExt, WidgetExt};
+use relm4::factory::FactoryComponent;
+use relm4::FactorySender;
+
+/// Size of application icons in the mixer
+const ICON_SIZE: i32 = 24;
+const FALLBACK_ICON: &str = "audio-x-generic-symbolic";
+
+/// Application in the mixer with its own volume and mute
+#[derive(Debug)]
+pub struct AudioStream {
+    pub index: u32,
+    stream: Stream,
+    icon: String,
+    max_volume: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum AudioStreamMessage {
+    Update(Stream),
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamControl {
+    Volume(f64),
+    Mute(bool),
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for AudioStream {
+    type Init = (Stream, f64);
+    type Input = AudioStreamMessage;
+    type Output = (u32, StreamControl);
+    type ParentWidget = gtk::Box;
+    type CommandOutput = ();
+
+    view! {
+        #[root]
+        gtk::Box {
+            add_css_class: "audio__stream",
+            set_spacing: 8,
+
+            gtk::Image {
+                set_pixel_size: ICON_SIZE,
+                #[watch]
+                set_icon_name: Some(&self.icon),
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_hexpand: true,
+
+                gtk::Label {
+                    add_css_class: "audio__stream-name",
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    set_max_width_chars: 30,
+                    #[watch]
+                    set_label: &self.stream.name,
+                    #[watch]
+                    set_tooltip_text: self.stream.media.as_deref(),
+                },
+
+                gtk::Scale {
+                    set_draw_value: false,
+                    // Streams can be raised above the limit by the application itself
+                    #[watch]
+                    set_range: (0f64, self.max_volume.max(self.stream.volume)),
+                    #[watch]
+                    set_value: self.stream.volume,
+                    connect_change_value[sender, index = self.index] => move |_, _, value| {
+                        _ = sender.output((index, StreamControl::Volume(value)));
+                        Propagation::Proceed
+                    },
+                },
+            },
+
+            gtk::ToggleButton {
+                add_css_class: "flat",
+                set_valign: gtk::Align::Center,
+                set_tooltip_text: Some("Mute"),
+                #[watch]
+                set_active: self.stream.mute,
+                #[watch]
+                set_icon_name: if self.stream.mute {
+                    "audio-volume-muted-symbolic"
+                } else {
+                    "audio-volume-high-symbolic"
+                },
+                // Already toggled here, unlike when the state is set from outside
+                connect_clicked[sender, index = self.index] => move |button| {
+                    _ = sender.output((index, StreamControl::Mute(button.is_active())));
+                },
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
+        let (stream, max_volume) = init;
+        Self {
+            index: stream.index,
+            icon: stream_icon(&stream),
+            stream,
+            max_volume,
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: FactorySender<Self>) {
+        let AudioStreamMessage::Update(stream) = message;
+        if stream.icon_name != self.stream.icon_name || stream.binary != self.stream.binary {
+            self.icon = stream_icon(&stream);
+        }
+        self.stream = stream;
+    }
+}
+
+/// Icon the application asked for, or the one named like its binary
+fn stream_icon(stream: &Stream) -> String {
+    let Some(display) = gdk::Display::default() else {
+        return FALLBACK_ICON.to_string();
+    };
+    let theme = gtk::IconTheme::for_display(&display);
+    let binary = stream.binary.as_deref().map(str::to_lowercase);
+    [stream.icon_name.clone(), stream.binary.clone(), binary]
+        .into_iter()
+        .flatten()
+        .find(|it| theme.has_icon(it))
+        .unwrap_or_else(|| FALLBACK_ICON.to_string())
+}
@@ -0,0 +1,211 @@
+use chrono::{Datelike, Days, Locale, Months, NaiveDate, Weekday};
+use gtk::prelude::{BoxExt, ButtonExt, GridExt, OrientableExt, WidgetExt};
+use log::error;
+use relm4::{ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent};
+use serde::Deserialize;
+
+const WEEKS: usize = 6;
+
+pub struct Calendar {
+    /// First day of the shown month
+    month: NaiveDate,
+    today: NaiveDate,
+    first_weekday: Weekday,
+    week_numbers: bool,
+    locale: Locale,
+    days: Vec<gtk::Label>,
+    weeks: Vec<gtk::Label>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CalendarInit {
+    /// Show ISO week numbers column
+    pub week_numbers: bool,
+    /// e.g. "monday" or "sun"
+    pub first_weekday: String,
+}
+
+impl Default for CalendarInit {
+    fn default() -> Self {
+        Self {
+            week_numbers: false,
+            first_weekday: "monday".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CalendarMessage {
+    /// Resets shown month to the one containing `today`
+    Show {
+        today: NaiveDate,
+    },
+    PreviousMonth,
+    NextMonth,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for Calendar {
+    type Input = CalendarMessage;
+    type Output = ();
+    type Init = (CalendarInit, Locale);
+
+    view! {
+        #[root]
+        gtk::Box {
+            add_css_class: "calendar",
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 8,
+
+            gtk::CenterBox {
+                #[wrap(Some)]
+                set_start_widget = &gtk::Button {
+                    set_icon_name: "go-previous-symbolic",
+                    add_css_class: "calendar__nav",
+                    connect_clicked => CalendarMessage::PreviousMonth,
+                },
+                #[wrap(Some)]
+                set_center_widget = &gtk::Label {
+                    #[watch]
+                    set_label: &model.title(),
+                    add_css_class: "calendar__title",
+                },
+                #[wrap(Some)]
+                set_end_widget = &gtk::Button {
+                    set_icon_name: "go-next-symbolic",
+                    add_css_class: "calendar__nav",
+                    connect_clicked => CalendarMessage::NextMonth,
+                },
+            },
+
+            #[name = "grid"]
+            gtk::Grid {
+                set_row_homogeneous: true,
+                set_column_homogeneous: true,
+                set_row_spacing: 2,
+                set_column_spacing: 2,
+            },
+        }
+    }
+
+    fn init(
+        (init, locale): Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let first_weekday = init.first_weekday.parse::<Weekday>().unwrap_or_else(|_| {
+            error!("Unknown weekday {:?}, using monday", init.first_weekday);
+            Weekday::Mon
+        });
+        let today = chrono::Local::now().date_naive();
+        let mut model = Self {
+            month: first_of_month(today),
+            today,
+            first_weekday,
+            week_numbers: init.week_numbers,
+            locale,
+            days: Vec::with_capacity(WEEKS * 7),
+            weeks: Vec::with_capacity(WEEKS),
+        };
+        let widgets = view_output!();
+
+        let column = i32::from(model.week_numbers);
+        let dates = month_grid(model.month, model.first_weekday);
+        for (index, date) in dates.iter().take(7).enumerate() {
+            let label = gtk::Label::new(Some(&date.format_localized("%a", locale).to_string()));
+            label.add_css_class("calendar__weekday");
+            widgets.grid.attach(&label, column + index as i32, 0, 1, 1);
+        }
+        for row in 1..=WEEKS as i32 {
+            if model.week_numbers {
+                let label = gtk::Label::new(None);
+                label.add_css_class("calendar__week");
+                widgets.grid.attach(&label, 0, row, 1, 1);
+                model.weeks.push(label);
+            }
+            for index in 0..7 {
+                let label = gtk::Label::new(None);
+                label.add_css_class("calendar__day");
+                widgets.grid.attach(&label, column + index, row, 1, 1);
+                model.days.push(label);
+            }
+        }
+        model.refresh();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+        let month = match message {
+            CalendarMessage::Show { today } => {
+                self.today = today;
+                Some(first_of_month(today))
+            }
+            CalendarMessage::PreviousMonth => self.month.checked_sub_months(Months::new(1)),
+            CalendarMessage::NextMonth => self.month.checked_add_months(Months::new(1)),
+        };
+        if let Some(month) = month {
+            self.month = month;
+        }
+        self.refresh();
+    }
+}
+
+impl Calendar {
+    fn title(&self) -> String {
+        self.month
+            .format_localized("%B %Y", self.locale)
+            .to_string()
+    }
+
+    fn refresh(&self) {
+        let dates = month_grid(self.month, self.first_weekday);
+        for (label, date) in self.days.iter().zip(&dates) {
+            label.set_label(&date.day().to_string());
+            label.set_class_active("today", *date == self.today);
+            label.set_class_active("other-month", date.month() != self.month.month());
+        }
+        for (label, week) in self.weeks.iter().zip(dates.chunks(7)) {
+            // ISO week starts on monday, so it is the one to number the row
+            let monday = week.iter().find(|it| it.weekday() == Weekday::Mon);
+            if let Some(monday) = monday {
+                label.set_label(&monday.iso_week().week().to_string());
+            }
+        }
+    }
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// Six full weeks of dates covering the month, starting from `first_weekday`
+fn month_grid(month: NaiveDate, first_weekday: Weekday) -> Vec<NaiveDate> {
+    let month = first_of_month(month);
+    let offset = month.weekday().days_since(first_weekday);
+    let start = month - Days::new(offset.into());
+    (0..(WEEKS * 7) as u64)
+        .map(|it| start + Days::new(it))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::month_grid;
+    use chrono::{NaiveDate, Weekday};
+
+    #[test]
+    fn test_month_grid() {
+        let month = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+
+        let grid = month_grid(month, Weekday::Mon);
+        assert_eq!(grid.len(), 42);
+        assert_eq!(grid[0], NaiveDate::from_ymd_opt(2026, 9, 28).unwrap());
+        assert_eq!(grid[3], month);
+
+        let grid = month_grid(month, Weekday::Sun);
+        assert_eq!(grid[0], NaiveDate::from_ymd_opt(2026, 9, 27).unwrap());
+        assert_eq!(grid[4], month);
+    }
+}
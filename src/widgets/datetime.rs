@@ -1,24 +1,181 @@
-use gtk::prelude::WidgetExt;
+use crate::dbus::Login1ManagerProxy;
+use crate::ext::ParentFor;
+use crate::widgets::{Calendar, CalendarInit, CalendarMessage};
+use crate::workers::{AgendaInit, AgendaWorker, Occurrence};
+use chrono::format::{Fixed, Item, Numeric, StrftimeItems};
+use chrono::{Locale, NaiveDate, NaiveDateTime, Timelike, Utc};
+use futures::StreamExt;
+use gtk::glib::Propagation;
+use gtk::prelude::{BoxExt, GestureSingleExt, OrientableExt, PopoverExt, WidgetExt};
+use gtk::EventControllerScrollFlags;
+use log::{debug, error, warn};
 use relm4::prelude::AsyncComponentParts;
 use relm4::prelude::SimpleAsyncComponent;
+use relm4::{
+    AsyncComponentSender, Component, ComponentController, Controller, RelmWidgetExt,
+    WorkerController,
+};
+use serde::Deserialize;
+use std::time::Duration;
+
+const DEFAULT_FORMAT: &str = "%H:%M:%S %d.%m.%Y";
+/// Tick rate for formats showing fractions of a second
+const SUBSECOND: Duration = Duration::from_millis(100);
+/// Longest sleep between ticks, so a wall clock change is picked up within a minute
+const MAX_SLEEP: Duration = Duration::from_secs(60);
 
-#[allow(dead_code)]
 pub struct DateTime {
     label: String,
+    tooltip: Option<String>,
     format: String,
-    task: tokio::task::JoinHandle<()>,
+    alt_format: Option<String>,
+    tooltip_format: Option<String>,
+    show_alt: bool,
+    zone: Zone,
+    /// Extra named zones, shown one at a time after the main one
+    zones: Vec<(String, Zone)>,
+    /// 0 is the main zone, others index `zones` shifted by one
+    zone_index: usize,
+    zone_format: String,
+    locale: Locale,
+    calendar: Controller<Calendar>,
+    agenda: Option<WorkerController<AgendaWorker>>,
+    events: Vec<Occurrence>,
+    agenda_text: String,
+    soon_minutes: u32,
+    meeting_soon: bool,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DateTimeInit {
+    /// strftime format of the label
+    pub format: String,
+    /// Format shown instead of the main one after a right click
+    pub alt_format: Option<String>,
+    pub tooltip_format: Option<String>,
+    /// IANA time zone, e.g. "Europe/Berlin". Local time when not set
+    pub timezone: Option<String>,
+    /// Locale for month and weekday names, e.g. "ru_RU". Taken from environment when not set
+    pub locale: Option<String>,
+    /// World clock, cycled by middle click or scroll
+    pub zones: Vec<NamedZone>,
+    /// Format of each zone in the tooltip
+    pub zone_format: String,
+    pub calendar: CalendarInit,
+    pub agenda: AgendaInit,
+}
+
+impl Default for DateTimeInit {
+    fn default() -> Self {
+        Self {
+            format: DEFAULT_FORMAT.to_string(),
+            alt_format: None,
+            tooltip_format: None,
+            timezone: None,
+            locale: None,
+            zones: Vec::new(),
+            zone_format: "%H:%M".to_string(),
+            calendar: CalendarInit::default(),
+            agenda: AgendaInit::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NamedZone {
+    /// Shown before time, e.g. "Tokyo"
+    pub name: String,
+    /// IANA time zone, e.g. "Asia/Tokyo"
+    pub timezone: String,
+}
+
+#[derive(Debug)]
+pub enum DateTimeMessage {
+    Tick,
+    ToggleFormat,
+    ShowCalendar,
+    /// Scroll while calendar is open changes its month
+    ScrollCalendar(f64),
+    /// Shows next or previous zone of the world clock
+    CycleZone(i32),
+    Agenda(Vec<Occurrence>),
+    /// System woke up from suspend, timers may be late
+    Resumed,
 }
 
 #[relm4::component(pub async)]
 impl SimpleAsyncComponent for DateTime {
-    type Input = ();
+    type Input = DateTimeMessage;
     type Output = ();
-    type Init = ();
+    type Init = DateTimeInit;
 
     view! {
         #[root]
         gtk::Box {
             add_css_class: "module",
+            add_css_class: "datetime",
+            #[watch]
+            set_tooltip_text: model.tooltip.as_deref(),
+            #[watch]
+            set_class_active: ("meeting-soon", model.meeting_soon),
+
+            set_parent_for: popover = &gtk::Popover {
+                add_css_class: "menu",
+
+                #[wrap(Some)]
+                set_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+
+                    append: model.calendar.widget(),
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &model.agenda_text,
+                        #[watch]
+                        set_visible: !model.agenda_text.is_empty(),
+                        set_halign: gtk::Align::Start,
+                        add_css_class: "calendar__agenda",
+                    },
+                },
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gtk::gdk::BUTTON_PRIMARY,
+                connect_pressed[sender, popover] => move |_, _, _, _| {
+                    sender.input(DateTimeMessage::ShowCalendar);
+                    popover.popup();
+                }
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gtk::gdk::BUTTON_SECONDARY,
+                connect_pressed[sender] => move |_, _, _, _| {
+                    sender.input(DateTimeMessage::ToggleFormat);
+                }
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gtk::gdk::BUTTON_MIDDLE,
+                connect_pressed[sender] => move |_, _, _, _| {
+                    sender.input(DateTimeMessage::CycleZone(1));
+                }
+            },
+
+            add_controller = gtk::EventControllerScroll::new(EventControllerScrollFlags::VERTICAL) {
+                connect_scroll[sender, popover] => move |_, _, y| {
+                    if popover.is_visible() {
+                        sender.input(DateTimeMessage::ScrollCalendar(y));
+                    } else if y > 0f64 {
+                        sender.input(DateTimeMessage::CycleZone(1));
+                    } else if y < 0f64 {
+                        sender.input(DateTimeMessage::CycleZone(-1));
+                    }
+                    Propagation::Stop
+                }
+            },
 
             gtk::Label {
                 #[watch]
@@ -28,43 +185,394 @@ impl SimpleAsyncComponent for DateTime {
     }
 
     async fn init(
-        _init: Self::Init,
+        init: Self::Init,
         root: Self::Root,
         _sender: relm4::AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
-        let time = chrono::Local::now();
-        let format = "%H:%M:%S %d.%m.%Y";
-        let label = time.format(format).to_string();
-
-        let task = {
+        {
             let sender_clone = _sender.clone();
             relm4::spawn(async move {
-                let mut interval = tokio::time::interval(std::time::Duration::from_millis(500));
-                loop {
-                    interval.tick().await; // Wait for the next tick
-                    sender_clone.input(());
+                if let Err(err) = watch_resume(sender_clone).await {
+                    error!("Error watching for resume from suspend: {err}");
                 }
-            })
-        };
+            });
+        }
 
-        let model = Self {
-            format: format.to_string(),
-            label,
-            task,
+        let format = valid_format(Some(init.format)).unwrap_or_else(|| DEFAULT_FORMAT.to_string());
+        let locale = parse_locale(init.locale.as_deref());
+        let calendar = Calendar::builder().launch((init.calendar, locale)).detach();
+        let agenda = (!init.agenda.paths.is_empty()).then(|| {
+            AgendaWorker::builder()
+                .detach_worker(init.agenda.clone())
+                .forward(_sender.input_sender(), DateTimeMessage::Agenda)
+        });
+        let mut model = Self {
+            label: String::new(),
+            tooltip: None,
+            format,
+            alt_format: valid_format(init.alt_format),
+            tooltip_format: valid_format(init.tooltip_format),
+            show_alt: false,
+            zone: Zone::parse(init.timezone.as_deref()),
+            zones: init
+                .zones
+                .iter()
+                .map(|it| (it.name.clone(), Zone::parse(Some(&it.timezone))))
+                .collect(),
+            zone_index: 0,
+            zone_format: valid_format(Some(init.zone_format))
+                .unwrap_or_else(|| "%H:%M".to_string()),
+            locale,
+            calendar,
+            agenda,
+            events: Vec::new(),
+            agenda_text: String::new(),
+            soon_minutes: init.agenda.soon_minutes,
+            meeting_soon: false,
+            task: None,
         };
+        model.update_time();
+        model.schedule_ticks(_sender.clone());
+        let sender = _sender.clone();
         let widgets = view_output!();
 
         AsyncComponentParts { model, widgets }
     }
 
-    async fn update(&mut self, _message: Self::Input, _sender: relm4::AsyncComponentSender<Self>) {
-        let () = _message;
+    async fn update(&mut self, message: Self::Input, _sender: relm4::AsyncComponentSender<Self>) {
+        match message {
+            DateTimeMessage::Tick => {}
+            DateTimeMessage::ToggleFormat => {
+                self.show_alt = !self.show_alt && self.alt_format.is_some();
+                self.schedule_ticks(_sender);
+            }
+            DateTimeMessage::ShowCalendar => {
+                let today = self.zone.today();
+                self.calendar.emit(CalendarMessage::Show { today });
+            }
+            DateTimeMessage::ScrollCalendar(y) => {
+                if y < 0f64 {
+                    self.calendar.emit(CalendarMessage::PreviousMonth);
+                } else if y > 0f64 {
+                    self.calendar.emit(CalendarMessage::NextMonth);
+                }
+                return;
+            }
+            DateTimeMessage::CycleZone(delta) => {
+                let count = self.zones.len() as i64 + 1;
+                self.zone_index =
+                    (self.zone_index as i64 + i64::from(delta)).rem_euclid(count) as usize;
+            }
+            DateTimeMessage::Agenda(events) => {
+                self.events = events;
+            }
+            DateTimeMessage::Resumed => {
+                debug!("Resumed from suspend, rescheduling clock");
+                self.schedule_ticks(_sender);
+            }
+        }
         self.update_time();
     }
 }
 
 impl DateTime {
+    fn label_format(&self) -> &str {
+        match (&self.alt_format, self.show_alt) {
+            (Some(alt_format), true) => alt_format,
+            _ => &self.format,
+        }
+    }
+
+    /// Smallest unit of time shown anywhere in the module
+    fn precision(&self) -> Precision {
+        let formats = [
+            Some(self.label_format()),
+            self.tooltip_format.as_deref(),
+            (!self.zones.is_empty()).then_some(self.zone_format.as_str()),
+        ];
+        let precision = formats
+            .into_iter()
+            .flatten()
+            .map(|it| Precision::of(it, self.locale))
+            .min()
+            .unwrap_or(Precision::Day);
+        // `meeting-soon` is checked every minute
+        match self.agenda {
+            Some(_) => precision.min(Precision::Minute),
+            None => precision,
+        }
+    }
+
+    /// Restarts ticking, waking up only when the shown time changes
+    fn schedule_ticks(&mut self, sender: AsyncComponentSender<Self>) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        let precision = self.precision();
+        let zone = self.zone;
+        debug!("Clock precision is {precision:?}");
+        self.task = Some(relm4::spawn(async move {
+            loop {
+                let delay = precision.until_next(zone.naive(Utc::now()));
+                tokio::time::sleep(delay).await;
+                sender.input(DateTimeMessage::Tick);
+            }
+        }));
+    }
+
     fn update_time(&mut self) {
-        self.label = chrono::Local::now().format(&self.format).to_string();
+        let now = Utc::now();
+        let format = self.label_format();
+        self.label = match self
+            .zone_index
+            .checked_sub(1)
+            .and_then(|it| self.zones.get(it))
+        {
+            Some((name, zone)) => format!("{name} {}", zone.format(now, format, self.locale)),
+            None => self.zone.format(now, format, self.locale),
+        };
+        self.update_agenda(now);
+        let sections = [
+            self.tooltip_format
+                .as_ref()
+                .map(|format| self.zone.format(now, format, self.locale)),
+            self.world_clock(now),
+            Some(self.agenda_text.clone()),
+        ];
+        let tooltip = sections
+            .into_iter()
+            .flatten()
+            .filter(|it| !it.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        self.tooltip = (!tooltip.is_empty()).then_some(tooltip);
+    }
+
+    /// Time in every configured zone with day offset from the main zone
+    fn world_clock(&self, now: chrono::DateTime<Utc>) -> Option<String> {
+        if self.zones.is_empty() {
+            return None;
+        }
+        let today = self.zone.naive(now).date();
+        let lines = self
+            .zones
+            .iter()
+            .map(|(name, zone)| {
+                let time = zone.format(now, &self.zone_format, self.locale);
+                let offset = (zone.naive(now).date() - today).num_days();
+                match offset {
+                    0 => format!("{name}  {time}"),
+                    offset => format!("{name}  {time} ({offset:+}d)"),
+                }
+            })
+            .collect::<Vec<String>>();
+        Some(lines.join("\n"))
+    }
+
+    /// Lists upcoming events grouped by day and checks if one starts soon
+    fn update_agenda(&mut self, now: chrono::DateTime<Utc>) {
+        let soon = now + chrono::Duration::minutes(self.soon_minutes.into());
+        self.meeting_soon = self
+            .events
+            .iter()
+            .any(|it| !it.all_day && it.start >= now && it.start <= soon);
+
+        let today = self.zone.today();
+        let mut lines = Vec::new();
+        let mut day = None;
+        for event in self.events.iter().filter(|it| it.end > now) {
+            let start = self.zone.naive(event.start);
+            let date = start.date().max(today);
+            if day != Some(date) {
+                day = Some(date);
+                lines.push(if date == today {
+                    "Today".to_string()
+                } else {
+                    date.format_localized("%a %d %B", self.locale).to_string()
+                });
+            }
+            let time = if event.all_day {
+                "All day".to_string()
+            } else {
+                let end = self.zone.naive(event.end);
+                format!("{}–{}", start.format("%H:%M"), end.format("%H:%M"))
+            };
+            let mut line = format!("  {time} {}", event.summary);
+            if let Some(location) = &event.location {
+                line.push_str(&format!(" ({location})"));
+            }
+            lines.push(line);
+        }
+        self.agenda_text = lines.join("\n");
+    }
+}
+
+/// Smallest unit of time a format shows, finest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precision {
+    Subsecond,
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Precision {
+    fn of(format: &str, locale: Locale) -> Self {
+        StrftimeItems::new_with_locale(format, locale)
+            .filter_map(|item| match item {
+                Item::Numeric(Numeric::Nanosecond, _)
+                | Item::Fixed(
+                    Fixed::Nanosecond
+                    | Fixed::Nanosecond3
+                    | Fixed::Nanosecond6
+                    | Fixed::Nanosecond9,
+                ) => Some(Self::Subsecond),
+                Item::Numeric(Numeric::Second | Numeric::Timestamp, _)
+                | Item::Fixed(Fixed::RFC2822 | Fixed::RFC3339) => Some(Self::Second),
+                Item::Numeric(Numeric::Minute, _) => Some(Self::Minute),
+                Item::Numeric(Numeric::Hour | Numeric::Hour12, _)
+                | Item::Fixed(Fixed::LowerAmPm | Fixed::UpperAmPm) => Some(Self::Hour),
+                _ => None,
+            })
+            .min()
+            .unwrap_or(Self::Day)
+    }
+
+    /// Time left until the unit changes, capped by [`MAX_SLEEP`]
+    fn until_next(&self, time: NaiveDateTime) -> Duration {
+        // Leap second is represented by nanoseconds past one second
+        let nanos = time.nanosecond().min(999_999_999);
+        let to_second = Duration::from_secs(1) - Duration::from_nanos(nanos.into());
+        let seconds = match self {
+            Self::Subsecond => return SUBSECOND,
+            Self::Second => 0,
+            Self::Minute => 59 - time.second(),
+            Self::Hour => 3599 - time.minute() * 60 - time.second(),
+            Self::Day => 86399 - time.num_seconds_from_midnight(),
+        };
+        (to_second + Duration::from_secs(seconds.into())).min(MAX_SLEEP)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+impl Zone {
+    fn parse(name: Option<&str>) -> Self {
+        let Some(name) = name else {
+            return Self::Local;
+        };
+        match name.parse::<chrono_tz::Tz>() {
+            Ok(tz) => Self::Named(tz),
+            Err(err) => {
+                error!("Unknown time zone {name:?}, using local time: {err}");
+                Self::Local
+            }
+        }
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.naive(Utc::now()).date()
+    }
+
+    /// Wall clock time in this zone
+    fn naive(&self, time: chrono::DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Local => time.with_timezone(&chrono::Local).naive_local(),
+            Zone::Named(tz) => time.with_timezone(tz).naive_local(),
+        }
+    }
+
+    fn format(&self, now: chrono::DateTime<Utc>, format: &str, locale: Locale) -> String {
+        match self {
+            Zone::Local => now
+                .with_timezone(&chrono::Local)
+                .format_localized(format, locale)
+                .to_string(),
+            Zone::Named(tz) => now
+                .with_timezone(tz)
+                .format_localized(format, locale)
+                .to_string(),
+        }
+    }
+}
+
+/// Monotonic timers stop during suspend, so ticks are rescheduled on resume
+async fn watch_resume(sender: AsyncComponentSender<DateTime>) -> zbus::Result<()> {
+    let connection = zbus::Connection::system().await?;
+    let manager = Login1ManagerProxy::new(&connection).await?;
+    let mut signals = manager.receive_prepare_for_sleep().await?;
+    while let Some(signal) = signals.next().await {
+        if !signal.args()?.start {
+            sender.input(DateTimeMessage::Resumed);
+        }
+    }
+    Ok(())
+}
+
+/// Formatting with invalid strftime string panics, so such formats are dropped
+fn valid_format(format: Option<String>) -> Option<String> {
+    let format = format?;
+    if StrftimeItems::new(&format).any(|it| it == Item::Error) {
+        error!("Invalid date format {format:?}");
+        return None;
+    }
+    Some(format)
+}
+
+/// Parses locale like "ru_RU" or "ru_RU.UTF-8", falling back to LC_ALL, LC_TIME and LANG
+fn parse_locale(name: Option<&str>) -> Locale {
+    let name = name.map(str::to_string).or_else(|| {
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|it| std::env::var(it).ok())
+            .find(|it| !it.is_empty())
+    });
+    let Some(name) = name else {
+        return Locale::POSIX;
+    };
+    let name = name.split('.').next().unwrap_or_default();
+    if name == "C" || name == "POSIX" {
+        return Locale::POSIX;
+    }
+    Locale::try_from(name).unwrap_or_else(|_| {
+        warn!("Unknown locale {name:?}, using POSIX");
+        Locale::POSIX
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Precision;
+    use chrono::{Locale, NaiveDate};
+    use std::time::Duration;
+
+    #[test]
+    fn test_precision() {
+        let precision = |format| Precision::of(format, Locale::POSIX);
+        assert_eq!(precision("%H:%M:%S %d.%m.%Y"), Precision::Second);
+        assert_eq!(precision("%R %a"), Precision::Minute);
+        assert_eq!(precision("%X"), Precision::Second);
+        assert_eq!(precision("%I %p"), Precision::Hour);
+        assert_eq!(precision("%d %B"), Precision::Day);
+        assert_eq!(precision("%T%.3f"), Precision::Subsecond);
+    }
+
+    #[test]
+    fn test_until_next() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+
+        let time = date.and_hms_milli_opt(23, 59, 30, 250).unwrap();
+        let until_next = |precision: Precision| precision.until_next(time).as_millis();
+        assert_eq!(until_next(Precision::Second), 750);
+        assert_eq!(until_next(Precision::Minute), 29_750);
+        assert_eq!(until_next(Precision::Day), 29_750);
+
+        let time = date.and_hms_milli_opt(12, 0, 30, 250).unwrap();
+        assert_eq!(Precision::Hour.until_next(time), Duration::from_secs(60));
     }
 }
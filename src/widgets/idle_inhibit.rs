@@ -0,0 +1,177 @@
+use crate::dbus::Login1ManagerProxy;
+use crate::wayland::{IdleInhibitManager, IdleInhibitor};
+use chrono::Local;
+use gtk::prelude::{ButtonExt, NativeExt, WidgetExt};
+use log::{error, info};
+use relm4::{Component, ComponentParts, ComponentSender, RelmWidgetExt};
+use serde::Deserialize;
+use std::time::Duration;
+use zbus::zvariant::OwnedFd;
+
+pub struct IdleInhibit {
+    active: bool,
+    manager: Option<IdleInhibitManager>,
+    inhibitor: Option<IdleInhibitor>,
+    logind: bool,
+    _logind_lock: Option<OwnedFd>,
+    timeout: Option<Duration>,
+    /// When the inhibitor turns itself off
+    off_at: Option<chrono::DateTime<Local>>,
+    timer: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IdleInhibitInit {
+    /// Also take a logind "idle" inhibitor lock, for idle daemons that honor it
+    pub logind: bool,
+    /// Turns off by itself after this many minutes, 0 keeps it on
+    pub timeout: u64,
+}
+
+#[derive(Debug)]
+pub enum IdleInhibitMessage {
+    Toggle,
+    TimedOut,
+    LogindLock(OwnedFd),
+}
+
+#[relm4::component(pub)]
+impl Component for IdleInhibit {
+    type Init = IdleInhibitInit;
+    type Input = IdleInhibitMessage;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[root]
+        gtk::Button {
+            add_css_class: "module",
+            add_css_class: "hoverable",
+            add_css_class: "idle-inhibit",
+            #[watch]
+            set_class_active: ("active", model.active),
+            #[watch]
+            set_icon_name: model.icon(),
+            #[watch]
+            set_tooltip_text: Some(model.tooltip().as_str()),
+            connect_clicked => IdleInhibitMessage::Toggle,
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Self {
+            active: false,
+            manager: IdleInhibitManager::new(&root.display()),
+            inhibitor: None,
+            logind: init.logind,
+            _logind_lock: None,
+            timeout: (init.timeout > 0)
+                .then(|| Duration::from_secs(init.timeout.saturating_mul(60))),
+            off_at: None,
+            timer: None,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match message {
+            IdleInhibitMessage::Toggle if self.active => self.turn_off(),
+            IdleInhibitMessage::Toggle => self.turn_on(root, sender.clone()),
+            IdleInhibitMessage::TimedOut => {
+                info!("Idle inhibitor timed out");
+                self.turn_off();
+            }
+            IdleInhibitMessage::LogindLock(fd) => {
+                // Dropping the descriptor releases a lock that came too late
+                if self.active {
+                    self._logind_lock = Some(fd);
+                }
+            }
+        }
+        self.update_view(widgets, sender);
+    }
+}
+
+impl IdleInhibit {
+    fn turn_on(&mut self, root: &gtk::Button, sender: ComponentSender<Self>) {
+        let surface = root.native().and_then(|it| it.surface());
+        self.inhibitor = self
+            .manager
+            .as_mut()
+            .zip(surface)
+            .and_then(|(manager, surface)| manager.inhibit(&surface));
+        if self.inhibitor.is_none() && !self.logind {
+            return;
+        }
+        self.active = true;
+
+        if self.logind {
+            let sender = sender.clone();
+            relm4::spawn(async move {
+                match take_logind_lock().await {
+                    Ok(fd) => sender.input(IdleInhibitMessage::LogindLock(fd)),
+                    Err(err) => error!("Error taking logind idle lock: {err}"),
+                }
+            });
+        }
+
+        if let Some(timeout) = self.timeout {
+            self.off_at = chrono::Duration::from_std(timeout)
+                .ok()
+                .map(|it| Local::now() + it);
+            self.timer = Some(relm4::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                sender.input(IdleInhibitMessage::TimedOut);
+            }));
+        }
+    }
+
+    fn turn_off(&mut self) {
+        self.active = false;
+        self.inhibitor = None;
+        self._logind_lock = None;
+        self.off_at = None;
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        if self.active {
+            "changes-prevent-symbolic"
+        } else {
+            "changes-allow-symbolic"
+        }
+    }
+
+    fn tooltip(&self) -> String {
+        match (self.active, self.off_at) {
+            (true, Some(off_at)) => {
+                format!("Idle inhibitor on until {}", off_at.format("%H:%M"))
+            }
+            (true, None) => "Idle inhibitor on".to_string(),
+            (false, _) => "Idle inhibitor off".to_string(),
+        }
+    }
+}
+
+async fn take_logind_lock() -> zbus::Result<OwnedFd> {
+    let connection = zbus::Connection::system().await?;
+    let manager = Login1ManagerProxy::new(&connection).await?;
+    manager
+        .inhibit("idle", "rgb", "Idle inhibitor is on", "block")
+        .await
+}
@@ -1,36 +1,94 @@
-use crate::ext::{ForName, KeyboardLayoutEx};
+use crate::ext::{ForName, KeyboardLayoutEx, ParentFor};
+use crate::new_stateless_value_action;
 use crate::widgets::HyprlandMessage;
-use gtk::prelude::{ButtonExt, WidgetExt};
+use gtk::prelude::{ButtonExt, GestureSingleExt, PopoverExt, ToVariant, WidgetExt};
+use gtk::{gdk, gio};
+use hyprland::ctl::switch_xkb_layout::SwitchXKBLayoutCmdTypes;
 use hyprland::data::Keyboard;
+use hyprland::prelude::HyprDataActiveOptional;
 use hyprland::shared::HyprData;
 use log::error;
+use relm4::actions::{RelmAction, RelmActionGroup};
 use relm4::component::AsyncComponentParts;
 use relm4::component::SimpleAsyncComponent;
 use relm4::AsyncComponentSender;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use xkb_data::{KeyboardLayout, KeyboardVariant};
 
 #[derive(Debug, Clone)]
 pub struct Language {
     device: Arc<Mutex<Option<hyprland::data::Keyboard>>>,
-    layout: Arc<Mutex<Option<String>>>,
+    layout: Arc<Mutex<Option<LayoutInfo>>>,
+    layouts_menu: gio::Menu,
+    labels: HashMap<String, LayoutLabel>,
+    display: Vec<LayoutDisplay>,
+    remember: Option<WindowKey>,
+    /// Focused window key, see [`WindowKey`]
+    window: Option<String>,
+    window_layouts: WindowLayouts,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
 pub struct LanguageInit {
     pub device_name: String,
+    /// Custom labels by xkb layout name, either `us` or `us(intl)` for a variant
+    pub labels: HashMap<String, LayoutLabel>,
+    /// What to show on the button, in order. Shows code when empty
+    pub display: Vec<LayoutDisplay>,
+    /// Remembers layout per window and restores it when the window is focused again
+    pub remember: Option<WindowKey>,
 }
 
-#[allow(dead_code)]
 impl LanguageInit {
     pub fn new(device_name: &str) -> Self {
         Self {
             device_name: device_name.to_string(),
+            ..Default::default()
         }
     }
+}
 
-    pub fn new_for_string(device_name: String) -> Self {
-        Self { device_name }
-    }
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LayoutLabel {
+    /// Replaces short code, e.g. "EN"
+    pub text: Option<String>,
+    /// Replaces emoji flag
+    pub flag: Option<String>,
+    /// Icon name shown instead of emoji flag
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutDisplay {
+    Code,
+    Flag,
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowKey {
+    Address,
+    Class,
+}
+
+/// Last layout index used in each window, by [`WindowKey`]
+#[derive(Debug, Clone, Default)]
+struct WindowLayouts(HashMap<String, u8>);
+
+/// Resolved xkb layout currently active or listed in the popover
+#[derive(Debug, Clone, PartialEq)]
+struct LayoutInfo {
+    name: String,
+    variant: Option<String>,
+    code: String,
+    flag: Option<String>,
+    description: String,
 }
 
 #[relm4::component(pub async)]
@@ -46,17 +104,44 @@ impl SimpleAsyncComponent for Language {
             add_css_class: "hoverable",
             add_css_class: "language",
             #[watch]
-            set_label: &model.layout().unwrap_or_else(|| "NULL".to_string()).to_uppercase(),
-            // #[watch]
-            // set_tooltip_text: model.device().map(|it| it.name).as_deref(),
+            set_tooltip_text: model.tooltip().as_deref(),
+            set_parent_for: popover = &gtk::PopoverMenu::from_model(Some(&model.layouts_menu)),
             connect_clicked => move |_btn| {
                 // TODO find why freezes
                 relm4::spawn_blocking(|| {
-                    if let Err(e) = hyprland::ctl::switch_xkb_layout::call("all", hyprland::ctl::switch_xkb_layout::SwitchXKBLayoutCmdTypes::Next) {
+                    if let Err(e) = hyprland::ctl::switch_xkb_layout::call("all", SwitchXKBLayoutCmdTypes::Next) {
                         error!("Error switching keyboard layout: {e}");
                     };
                 });
-            }
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gdk::BUTTON_SECONDARY,
+                connect_pressed[popover] => move |_, _, _, _| {
+                    popover.popup();
+                }
+            },
+
+            #[wrap(Some)]
+            set_child = &gtk::Box {
+                set_spacing: 4,
+
+                gtk::Image {
+                    #[watch]
+                    set_icon_name: model.icon().as_deref(),
+                    #[watch]
+                    set_visible: model.icon().is_some(),
+                    add_css_class: "language__icon",
+                },
+
+                gtk::Label {
+                    #[watch]
+                    set_label: &model.label(),
+                    #[watch]
+                    set_visible: !model.label().is_empty(),
+                    add_css_class: "language__label",
+                },
+            },
         }
     }
 
@@ -68,32 +153,78 @@ impl SimpleAsyncComponent for Language {
         let model = Self {
             device: Arc::new(Mutex::new(None)),
             layout: Arc::new(Mutex::new(None)),
+            layouts_menu: gio::Menu::new(),
+            labels: _init.labels.clone(),
+            display: if _init.display.is_empty() {
+                vec![LayoutDisplay::Code]
+            } else {
+                _init.display.clone()
+            },
+            remember: _init.remember,
+            window: None,
+            window_layouts: WindowLayouts::default(),
         };
-        model.set_device_async(_init.device_name).await;
+        model.set_device_async(_init.device_name.clone()).await;
         let widgets = view_output!();
+
+        let device_name = _init.device_name;
+        let action: RelmAction<SwitchLayout> =
+            RelmAction::new_with_target_value(move |_action, index: u8| {
+                switch_layout(device_name.clone(), index);
+            });
+
+        let mut group = RelmActionGroup::<LanguageActionGroup>::new();
+        group.add_action(action);
+        group.register_for_widget(&widgets.popover);
+
         AsyncComponentParts { model, widgets }
     }
 
     async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
-        if let HyprlandMessage::SwitchKeyboardLayout {
-            keyboard_name,
-            layout_name,
-        } = message
-        {
-            let (keyboard_name, layout_name): (String, String) = {
-                if layout_name.is_empty() {
-                    let split = keyboard_name.split(',').collect::<Vec<&str>>();
-                    (split[0].to_string(), split[1].to_string())
-                } else {
-                    (keyboard_name, layout_name)
-                }
-            };
+        match message {
+            HyprlandMessage::SwitchKeyboardLayout {
+                keyboard_name,
+                layout_name,
+            } => {
+                let Some((keyboard_name, layout_name)) =
+                    split_layout_event(keyboard_name, layout_name)
+                else {
+                    error!("Unexpected keyboard layout event payload");
+                    return;
+                };
 
-            if self.device().is_some_and(|it| keyboard_name == it.name) {
-                self.set_layout(
-                    xkb_data::KeyboardLayout::find(&layout_name).and_then(|it| it.localized()),
-                );
+                if self.device().is_some_and(|it| keyboard_name == it.name) {
+                    self.set_layout(LayoutInfo::for_description(&layout_name));
+                    self.remember_layout();
+                }
+            }
+            HyprlandMessage::ActiveWindow { window } => {
+                let Some(remember) = self.remember else {
+                    return;
+                };
+                self.window = match window {
+                    Some(window) => Some(match remember {
+                        WindowKey::Address => window.window_address.to_string(),
+                        WindowKey::Class => window.window_class,
+                    }),
+                    None => hyprland::data::Client::get_active_async()
+                        .await
+                        .ok()
+                        .flatten()
+                        .map(|client| match remember {
+                            WindowKey::Address => client.address.to_string(),
+                            WindowKey::Class => client.class,
+                        }),
+                };
+                self.restore_layout();
             }
+            // Addresses aren't reused, so layouts of closed windows are dropped
+            HyprlandMessage::CloseWindow { address } => {
+                if self.remember == Some(WindowKey::Address) {
+                    self.window_layouts.forget(&address.to_string());
+                }
+            }
+            _ => {}
         }
     }
 }
@@ -104,16 +235,97 @@ impl Language {
         let keyboard = devices.keyboards.iter().find(|it| it.name == device_name);
         if let Ok(mut device) = self.device.lock() {
             *device = keyboard.cloned();
-            let layout = keyboard
-                .cloned()
-                .and_then(|it| xkb_data::KeyboardLayout::find(&it.active_keymap))
-                .and_then(|it| it.localized());
+            let layout = keyboard.and_then(|it| LayoutInfo::for_description(&it.active_keymap));
             self.set_layout(layout);
         }
+        self.build_layouts_menu();
         Some(())
     }
 
-    fn set_layout(&self, value: Option<String>) -> Option<()> {
+    fn remember_layout(&mut self) {
+        if self.remember.is_none() {
+            return;
+        }
+        let index = self.layout_index();
+        self.window_layouts.remember(self.window.as_deref(), index);
+    }
+
+    /// Switches to the layout last used in the focused window
+    fn restore_layout(&self) {
+        let Some(device) = self.device() else {
+            return;
+        };
+        if let Some(index) = self
+            .window_layouts
+            .restore(self.window.as_deref(), self.layout_index())
+        {
+            switch_layout(device.name, index);
+        }
+    }
+
+    /// Index of the active layout in the device's layout list
+    fn layout_index(&self) -> Option<u8> {
+        let device = self.device()?;
+        find_layout_index(&device.layout, &device.variant, &self.layout()?)
+    }
+
+    /// Fills popover menu with every layout configured on the device
+    fn build_layouts_menu(&self) {
+        self.layouts_menu.remove_all();
+        let Some(device) = self.device() else {
+            return;
+        };
+        let mut variants = device.variant.split(',').map(str::trim);
+        for (index, name) in device.layout.split(',').map(str::trim).enumerate() {
+            let Ok(index) = u8::try_from(index) else {
+                break;
+            };
+            let variant = variants.next().unwrap_or_default();
+            let label = match LayoutInfo::for_names(name, variant) {
+                Some(info) => format!("{}  {}", self.label_for(&info), info.description),
+                None => name.to_uppercase(),
+            };
+            let item = gio::MenuItem::new(Some(label.as_str()), None);
+            item.set_action_and_target_value(Some("language.switch"), Some(&index.to_variant()));
+            self.layouts_menu.append_item(&item);
+        }
+    }
+
+    fn custom_label(&self, info: &LayoutInfo) -> Option<&LayoutLabel> {
+        info.variant
+            .as_ref()
+            .and_then(|variant| self.labels.get(&format!("{}({variant})", info.name)))
+            .or_else(|| self.labels.get(&info.name))
+    }
+
+    fn label_for(&self, info: &LayoutInfo) -> String {
+        format_label(info, self.custom_label(info), &self.display)
+    }
+
+    fn label(&self) -> String {
+        match self.layout() {
+            Some(info) => self.label_for(&info),
+            None => "NULL".to_string(),
+        }
+    }
+
+    fn icon(&self) -> Option<String> {
+        if !self.display.contains(&LayoutDisplay::Flag) {
+            return None;
+        }
+        let info = self.layout()?;
+        self.custom_label(&info)?.icon.clone()
+    }
+
+    fn tooltip(&self) -> Option<String> {
+        let device = self.device()?;
+        Some(match self.layout() {
+            Some(info) => format!("{}\n{}", device.name, info.description),
+            None => device.name,
+        })
+    }
+
+    fn set_layout(&self, value: Option<LayoutInfo>) -> Option<()> {
         let mut layout = self.layout.lock().ok()?;
         *layout = value;
         Some(())
@@ -123,11 +335,108 @@ impl Language {
         self.device.lock().unwrap().clone()
     }
 
-    fn layout(&self) -> Option<String> {
+    fn layout(&self) -> Option<LayoutInfo> {
         self.layout.try_lock().ok()?.clone()
     }
 }
 
+impl WindowLayouts {
+    /// Unknown layouts keep the one remembered before
+    fn remember(&mut self, window: Option<&str>, index: Option<u8>) {
+        if let (Some(window), Some(index)) = (window, index) {
+            self.0.insert(window.to_string(), index);
+        }
+    }
+
+    /// Layout to switch to for the window, `None` when it's active already
+    fn restore(&self, window: Option<&str>, active: Option<u8>) -> Option<u8> {
+        let index = *self.0.get(window?)?;
+        (active != Some(index)).then_some(index)
+    }
+
+    fn forget(&mut self, window: &str) {
+        self.0.remove(window);
+    }
+}
+
+/// Position of the layout in Hyprland's comma separated layout and variant lists
+fn find_layout_index(layouts: &str, variants: &str, layout: &LayoutInfo) -> Option<u8> {
+    let mut variants = variants.split(',').map(str::trim);
+    let index = layouts.split(',').map(str::trim).position(|name| {
+        let variant = variants.next().unwrap_or_default();
+        name == layout.name && variant == layout.variant.as_deref().unwrap_or_default()
+    })?;
+    u8::try_from(index).ok()
+}
+
+/// Text for the layout according to configured display parts
+fn format_label(
+    info: &LayoutInfo,
+    custom: Option<&LayoutLabel>,
+    display: &[LayoutDisplay],
+) -> String {
+    display
+        .iter()
+        .filter_map(|part| match part {
+            LayoutDisplay::Code => custom
+                .and_then(|it| it.text.clone())
+                .or_else(|| Some(info.code.clone())),
+            LayoutDisplay::Flag if custom.is_some_and(|it| it.icon.is_some()) => None,
+            LayoutDisplay::Flag => custom
+                .and_then(|it| it.flag.clone())
+                .or_else(|| info.flag.clone()),
+            LayoutDisplay::Name => Some(info.description.clone()),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn switch_layout(device_name: String, index: u8) {
+    relm4::spawn_blocking(move || {
+        if let Err(e) =
+            hyprland::ctl::switch_xkb_layout::call(device_name, SwitchXKBLayoutCmdTypes::Id(index))
+        {
+            error!("Error switching keyboard layout to {index}: {e}");
+        };
+    });
+}
+
+/// Hyprland sends `keyboard,layout` in the keyboard name when the layout name is empty.
+/// Layout description itself may contain commas, so only the first one is a separator.
+fn split_layout_event(keyboard_name: String, layout_name: String) -> Option<(String, String)> {
+    if !layout_name.is_empty() {
+        return Some((keyboard_name, layout_name));
+    }
+    let (keyboard_name, layout_name) = keyboard_name.split_once(',')?;
+    Some((keyboard_name.to_string(), layout_name.to_string()))
+}
+
+impl LayoutInfo {
+    fn for_description(description: &str) -> Option<Self> {
+        let (layout, variant) = xkb_data::KeyboardLayout::find_variant(description)?;
+        Self::new(layout, variant)
+    }
+
+    fn for_names(name: &str, variant: &str) -> Option<Self> {
+        let (layout, variant) = xkb_data::KeyboardLayout::find_by_names(name, variant)?;
+        Self::new(layout, variant)
+    }
+
+    fn new(layout: KeyboardLayout, variant: Option<KeyboardVariant>) -> Option<Self> {
+        Some(Self {
+            name: layout.name().to_string(),
+            variant: variant.as_ref().map(|it| it.name().to_string()),
+            code: layout.short_label(variant.as_ref())?,
+            flag: layout.flag(),
+            description: variant
+                .as_ref()
+                .map(|it| it.description())
+                .unwrap_or_else(|| layout.description())
+                .to_string(),
+        })
+    }
+}
+
 impl ForName for Keyboard {
     fn for_name(name: &str) -> Option<Keyboard> {
         let devices = hyprland::data::Devices::get().ok()?;
@@ -141,3 +450,113 @@ impl ForName for Keyboard {
         Some(keyboard.clone())
     }
 }
+
+relm4::new_action_group!(LanguageActionGroup, "language");
+new_stateless_value_action!(SwitchLayout, LanguageActionGroup, "switch", u8);
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_layout_index, format_label, split_layout_event, LayoutDisplay, LayoutInfo,
+        LayoutLabel, WindowLayouts,
+    };
+
+    fn layout(name: &str, variant: Option<&str>) -> LayoutInfo {
+        LayoutInfo {
+            name: name.to_string(),
+            variant: variant.map(str::to_string),
+            code: name.to_uppercase(),
+            flag: Some("🏳".to_string()),
+            description: format!("{name} layout"),
+        }
+    }
+
+    #[test]
+    fn test_split_layout_event() {
+        assert_eq!(
+            split_layout_event("keyboard".to_string(), "Russian".to_string()),
+            Some(("keyboard".to_string(), "Russian".to_string()))
+        );
+        assert_eq!(
+            split_layout_event(
+                "keyboard,English (US, intl., with dead keys)".to_string(),
+                String::new()
+            ),
+            Some((
+                "keyboard".to_string(),
+                "English (US, intl., with dead keys)".to_string()
+            ))
+        );
+        assert_eq!(
+            split_layout_event("keyboard".to_string(), String::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_layout_index() {
+        let layouts = "us, us, ru";
+        let variants = ",intl,";
+        assert_eq!(
+            find_layout_index(layouts, variants, &layout("us", None)),
+            Some(0)
+        );
+        assert_eq!(
+            find_layout_index(layouts, variants, &layout("us", Some("intl"))),
+            Some(1)
+        );
+        assert_eq!(
+            find_layout_index(layouts, variants, &layout("ru", None)),
+            Some(2)
+        );
+        // Layout switched to outside of the configured list
+        assert_eq!(
+            find_layout_index(layouts, variants, &layout("de", None)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format_label() {
+        let info = layout("us", None);
+        let display = [
+            LayoutDisplay::Flag,
+            LayoutDisplay::Code,
+            LayoutDisplay::Name,
+        ];
+        assert_eq!(format_label(&info, None, &display), "🏳 US us layout");
+        let custom = LayoutLabel {
+            text: Some("EN".to_string()),
+            flag: None,
+            icon: Some("flag-us".to_string()),
+        };
+        assert_eq!(
+            format_label(&info, Some(&custom), &display[..2]),
+            "EN",
+            "Icon replaces the flag"
+        );
+    }
+
+    #[test]
+    fn test_window_layouts() {
+        let mut layouts = WindowLayouts::default();
+        layouts.remember(Some("firefox"), Some(1));
+        layouts.remember(Some("kitty"), Some(0));
+        // No focused window or a layout missing from the list isn't remembered
+        layouts.remember(None, Some(2));
+        layouts.remember(Some("kitty"), None);
+
+        // Switching between windows restores their layouts
+        assert_eq!(layouts.restore(Some("firefox"), Some(0)), Some(1));
+        assert_eq!(layouts.restore(Some("kitty"), Some(1)), Some(0));
+        assert_eq!(layouts.restore(Some("kitty"), Some(0)), None);
+        assert_eq!(layouts.restore(Some("kitty"), None), Some(0));
+        assert_eq!(layouts.restore(Some("thunar"), Some(1)), None);
+        assert_eq!(layouts.restore(None, Some(1)), None);
+
+        // Closed window is forgotten
+        layouts.forget("firefox");
+        assert_eq!(layouts.restore(Some("firefox"), Some(0)), None);
+        assert_eq!(layouts.restore(Some("kitty"), Some(1)), Some(0));
+    }
+}
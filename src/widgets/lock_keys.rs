@@ -0,0 +1,257 @@
+use crate::ext::ParentFor;
+use gtk::prelude::{BoxExt, PopoverExt, WidgetExt};
+use log::error;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use relm4::component::AsyncComponentParts;
+use relm4::component::SimpleAsyncComponent;
+use relm4::{AsyncComponentSender, RelmWidgetExt};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+pub struct LockKeys {
+    device_name: String,
+    _watcher: Option<RecommendedWatcher>,
+    state: LockState,
+    initialized: bool,
+    osd: Option<String>,
+    osd_generation: u32,
+    show_osd: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LockKeysInit {
+    /// Keyboard to watch as Hyprland names it, any keyboard counts when empty
+    pub device_name: String,
+    /// How often keyboard LEDs are re-read in case their changes weren't noticed,
+    /// in milliseconds
+    pub interval: u64,
+    /// Briefly show a popover when Caps Lock toggles
+    pub osd: bool,
+}
+
+impl Default for LockKeysInit {
+    fn default() -> Self {
+        Self {
+            device_name: String::new(),
+            interval: 1000,
+            osd: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockState {
+    caps: bool,
+    num: bool,
+    scroll: bool,
+}
+
+#[derive(Debug)]
+pub enum LockKeysMessage {
+    Refresh,
+    HideOsd(u32),
+}
+
+const OSD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+#[relm4::component(pub async)]
+impl SimpleAsyncComponent for LockKeys {
+    type Input = LockKeysMessage;
+    type Output = ();
+    type Init = LockKeysInit;
+
+    view! {
+        #[root]
+        gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::Crossfade,
+            set_transition_duration: 200,
+            #[watch]
+            set_reveal_child: model.state.caps || model.state.num || model.state.scroll,
+
+            // Popover is attached to the revealer, so it shows even when no lock is active
+            set_parent_for: popover = &gtk::Popover {
+                set_autohide: false,
+                set_has_arrow: false,
+                #[watch]
+                set_visible: model.osd.is_some(),
+
+                gtk::Label {
+                    #[watch]
+                    set_label: model.osd.as_deref().unwrap_or_default(),
+                    add_css_class: "lock-keys__osd",
+                },
+            },
+
+            gtk::Box {
+                add_css_class: "module",
+                add_css_class: "lock-keys",
+                set_spacing: 8,
+                #[watch]
+                set_class_active: ("caps-lock", model.state.caps),
+                #[watch]
+                set_class_active: ("num-lock", model.state.num),
+                #[watch]
+                set_class_active: ("scroll-lock", model.state.scroll),
+
+                gtk::Label {
+                    set_label: "CAPS",
+                    #[watch]
+                    set_visible: model.state.caps,
+                    add_css_class: "lock-keys__key",
+                },
+                gtk::Label {
+                    set_label: "NUM",
+                    #[watch]
+                    set_visible: model.state.num,
+                    add_css_class: "lock-keys__key",
+                },
+                gtk::Label {
+                    set_label: "SCRL",
+                    #[watch]
+                    set_visible: model.state.scroll,
+                    add_css_class: "lock-keys__key",
+                },
+            }
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        {
+            // Polling is a fallback for LEDs whose changes aren't reported
+            let sender = sender.clone();
+            let interval = Duration::from_millis(init.interval.max(50));
+            relm4::spawn(async move {
+                let mut interval = tokio::time::interval(interval);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                loop {
+                    interval.tick().await;
+                    sender.input(LockKeysMessage::Refresh);
+                }
+            });
+        }
+
+        let model = Self {
+            device_name: init.device_name,
+            _watcher: watch_leds(&sender),
+            state: LockState::default(),
+            initialized: false,
+            osd: None,
+            osd_generation: 0,
+            show_osd: init.osd,
+        };
+        let widgets = view_output!();
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
+        match message {
+            LockKeysMessage::Refresh => {
+                let Some(state) = LockState::read(&self.device_name).await else {
+                    return;
+                };
+                if self.initialized && state == self.state {
+                    return;
+                }
+                let caps_toggled = self.initialized && self.state.caps != state.caps;
+                self.state = state;
+                self.initialized = true;
+                if !self.show_osd || !caps_toggled {
+                    return;
+                }
+                self.osd = Some(
+                    if state.caps {
+                        "Caps Lock on"
+                    } else {
+                        "Caps Lock off"
+                    }
+                    .to_string(),
+                );
+                self.osd_generation = self.osd_generation.wrapping_add(1);
+                let generation = self.osd_generation;
+                relm4::spawn(async move {
+                    tokio::time::sleep(OSD_TIMEOUT).await;
+                    sender.input(LockKeysMessage::HideOsd(generation));
+                });
+            }
+            LockKeysMessage::HideOsd(generation) => {
+                if generation == self.osd_generation {
+                    self.osd = None;
+                }
+            }
+        }
+    }
+}
+
+/// Lock state is read from keyboard LEDs, cheap enough to poll
+const LEDS: &str = "/sys/class/leds";
+
+/// Refreshes right away when the kernel reports a lock LED change
+fn watch_leds(sender: &AsyncComponentSender<LockKeys>) -> Option<RecommendedWatcher> {
+    let sender = sender.clone();
+    let watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if event.kind.is_access() => {}
+            Ok(_) => sender.input(LockKeysMessage::Refresh),
+            Err(err) => error!("Error watching keyboard LEDs: {err}"),
+        });
+    let mut watcher = watcher
+        .map_err(|e| error!("Error creating keyboard LED watcher: {e}"))
+        .ok()?;
+    let leds = std::fs::read_dir(LEDS)
+        .map_err(|e| error!("Error reading {LEDS}: {e}"))
+        .ok()?;
+    for led in leds.flatten() {
+        let file_name = led.file_name().to_string_lossy().to_string();
+        if !matches!(
+            file_name.rsplit_once("::"),
+            Some((_, "capslock" | "numlock" | "scrolllock"))
+        ) {
+            continue;
+        }
+        let brightness = led.path().join("brightness");
+        if let Err(err) = watcher.watch(&brightness, RecursiveMode::NonRecursive) {
+            error!("Error watching {}: {err}", brightness.display());
+        }
+    }
+    Some(watcher)
+}
+
+impl LockState {
+    async fn read(device_name: &str) -> Option<Self> {
+        let mut leds = tokio::fs::read_dir(LEDS)
+            .await
+            .map_err(|e| error!("Error reading {LEDS}: {e}"))
+            .ok()?;
+        let mut state = Self::default();
+        while let Ok(Some(led)) = leds.next_entry().await {
+            let file_name = led.file_name().to_string_lossy().to_string();
+            let flag = match file_name.rsplit_once("::") {
+                Some((_, "capslock")) => &mut state.caps,
+                Some((_, "numlock")) => &mut state.num,
+                Some((_, "scrolllock")) => &mut state.scroll,
+                _ => continue,
+            };
+            if !device_name.is_empty() && !is_device(&led.path(), device_name).await {
+                continue;
+            }
+            if let Ok(brightness) = tokio::fs::read_to_string(led.path().join("brightness")).await {
+                *flag |= brightness.trim() != "0";
+            }
+        }
+        Some(state)
+    }
+}
+
+/// Hyprland names keyboards after the input device, lowercased with dashes for spaces
+async fn is_device(led: &Path, device_name: &str) -> bool {
+    tokio::fs::read_to_string(led.join("device").join("name"))
+        .await
+        .is_ok_and(|name| name.trim().to_lowercase().replace(' ', "-") == device_name)
+}
@@ -1,16 +1,32 @@
+mod audio;
+mod audio_device;
+mod audio_stream;
+mod calendar;
 mod datetime;
 mod focused;
+mod idle_inhibit;
 mod language;
+mod lock_keys;
+mod mpris;
+mod mpris_player;
 mod power_menu;
 mod systray;
+mod timer;
 mod tray_item;
+mod tray_tooltip;
 mod workspace;
 mod workspaces;
 
+pub use audio::*;
+pub use calendar::*;
 pub use datetime::*;
 pub use focused::*;
+pub use idle_inhibit::*;
 pub use language::*;
+pub use lock_keys::*;
+pub use mpris::*;
 pub use power_menu::*;
 pub use systray::*;
+pub use timer::*;
 pub use workspace::*;
 pub use workspaces::*;
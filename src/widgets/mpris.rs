@@ -0,0 +1,758 @@
+use crate::dbus::{MediaPlayer2Proxy, PlayerProxy, MPRIS_PATH, MPRIS_PLAYER, MPRIS_PREFIX};
+use crate::ext::ParentFor;
+use crate::widgets::mpris_player::{MprisPlayer, MprisPlayerMessage};
+use futures::StreamExt;
+use gtk::glib::Propagation;
+use gtk::prelude::{BoxExt, ButtonExt, FileExt, OrientableExt, PopoverExt, WidgetExt};
+use gtk::{gio, EventControllerScrollFlags};
+use log::{debug, error};
+use relm4::component::AsyncComponentParts;
+use relm4::factory::FactoryVecDeque;
+use relm4::prelude::SimpleAsyncComponent;
+use relm4::{AsyncComponentSender, RelmWidgetExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::names::InterfaceName;
+use zbus::zvariant::{ObjectPath, OwnedValue};
+use zbus::CacheProperties;
+
+pub struct Mpris {
+    players: FactoryVecDeque<MprisPlayer>,
+    /// Most recently playing first
+    order: Vec<String>,
+    states: HashMap<String, PlayerState>,
+    proxies: HashMap<String, PlayerProxy<'static>>,
+    tasks: HashMap<String, JoinHandle<()>>,
+    /// Runs only while some player is playing
+    tick: Option<JoinHandle<()>>,
+    scroll: MprisScroll,
+    seek_step: Duration,
+    volume_step: f64,
+    max_width: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MprisInit {
+    /// What scrolling over the module does
+    pub scroll: MprisScroll,
+    /// Seconds per scroll step when seeking
+    pub seek_step: u64,
+    /// Volume change per scroll step, from 0.0 to 1.0
+    pub volume_step: f64,
+    /// Track label is ellipsized after this many characters
+    pub max_width: i32,
+}
+
+impl Default for MprisInit {
+    fn default() -> Self {
+        Self {
+            scroll: MprisScroll::Volume,
+            seek_step: 5,
+            volume_step: 0.05,
+            max_width: 40,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MprisScroll {
+    #[default]
+    Volume,
+    Seek,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+/// Snapshot of a player, times are in microseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+    pub identity: String,
+    pub status: PlaybackStatus,
+    pub track_id: Option<String>,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    /// Only local art is shown, remote urls are ignored
+    pub art: Option<PathBuf>,
+    pub length: Option<i64>,
+    pub position: i64,
+    /// Players don't signal position changes, so it's counted from here while playing
+    pub position_at: Instant,
+    pub volume: Option<f64>,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_control: bool,
+    pub can_seek: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum PlayerControl {
+    PlayPause,
+    Next,
+    Previous,
+    SetPosition(i64),
+    Raise,
+}
+
+#[derive(Debug)]
+pub enum MprisMessage {
+    /// Player appeared on the bus
+    Added(String),
+    Removed(String),
+    State(String, PlayerState),
+    Control(String, PlayerControl),
+    /// Controls the player shown in the bar
+    ControlCurrent(PlayerControl),
+    Scroll(f64),
+    /// Moves position sliders while playing
+    Tick,
+}
+
+/// How often positions are updated while playing
+const TICK: Duration = Duration::from_secs(1);
+
+#[relm4::component(pub async)]
+impl SimpleAsyncComponent for Mpris {
+    type Init = MprisInit;
+    type Input = MprisMessage;
+    type Output = ();
+
+    view! {
+        #[root]
+        gtk::Box {
+            add_css_class: "module",
+            add_css_class: "mpris",
+            set_spacing: 4,
+            #[watch]
+            set_visible: model.current().is_some(),
+            #[watch]
+            set_class_active: ("playing", model.current().is_some_and(|it| it.status == PlaybackStatus::Playing)),
+
+            set_parent_for: popover = &gtk::Popover {
+                add_css_class: "menu",
+
+                #[wrap(Some)]
+                set_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    append: model.players.widget(),
+                },
+            },
+
+            add_controller = gtk::EventControllerScroll::new(EventControllerScrollFlags::VERTICAL) {
+                connect_scroll[sender] => move |_, _, y| {
+                    sender.input(MprisMessage::Scroll(y));
+                    Propagation::Stop
+                }
+            },
+
+            gtk::Button {
+                add_css_class: "mpris__button",
+                set_icon_name: "media-skip-backward-symbolic",
+                #[watch]
+                set_sensitive: model.current().is_some_and(|it| it.can_go_previous),
+                connect_clicked => MprisMessage::ControlCurrent(PlayerControl::Previous),
+            },
+
+            gtk::Button {
+                add_css_class: "mpris__button",
+                #[watch]
+                set_icon_name: play_pause_icon(model.current().map(|it| it.status)),
+                #[watch]
+                set_sensitive: model.current().is_some_and(|it| it.can_control),
+                connect_clicked => MprisMessage::ControlCurrent(PlayerControl::PlayPause),
+            },
+
+            gtk::Button {
+                add_css_class: "mpris__button",
+                set_icon_name: "media-skip-forward-symbolic",
+                #[watch]
+                set_sensitive: model.current().is_some_and(|it| it.can_go_next),
+                connect_clicked => MprisMessage::ControlCurrent(PlayerControl::Next),
+            },
+
+            gtk::Label {
+                add_css_class: "mpris__label",
+                set_ellipsize: gtk::pango::EllipsizeMode::End,
+                set_max_width_chars: model.max_width,
+                #[watch]
+                set_label: &model.current().map(PlayerState::label).unwrap_or_default(),
+                #[watch]
+                set_tooltip_text: model.current().map(|it| it.identity.as_str()),
+
+                add_controller = gtk::GestureClick::new() {
+                    set_button: gtk::gdk::BUTTON_PRIMARY,
+                    connect_pressed[popover] => move |_, _, _, _| {
+                        popover.popup();
+                    }
+                },
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        {
+            let sender = sender.clone();
+            relm4::spawn(async move {
+                if let Err(err) = watch_players(sender).await {
+                    error!("Error watching media players: {err}");
+                }
+            });
+        }
+
+        let model = Self {
+            players: FactoryVecDeque::builder()
+                .launch(gtk::Box::new(gtk::Orientation::Vertical, 8))
+                .forward(sender.input_sender(), |(name, control)| {
+                    MprisMessage::Control(name, control)
+                }),
+            order: Vec::new(),
+            states: HashMap::new(),
+            proxies: HashMap::new(),
+            tasks: HashMap::new(),
+            tick: None,
+            scroll: init.scroll,
+            seek_step: Duration::from_secs(init.seek_step),
+            volume_step: init.volume_step,
+            max_width: init.max_width,
+        };
+        let widgets = view_output!();
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
+        match message {
+            MprisMessage::Added(name) => {
+                if let Err(err) = self.add(name.clone(), sender).await {
+                    error!("Error adding media player {name}: {err}");
+                }
+            }
+            MprisMessage::Removed(name) => {
+                self.remove(&name);
+                self.update_tick(sender);
+            }
+            MprisMessage::State(name, state) => {
+                self.set_state(name, state);
+                self.update_tick(sender);
+            }
+            MprisMessage::Control(name, control) => self.control(&name, control),
+            MprisMessage::ControlCurrent(control) => {
+                if let Some(name) = self.current_name() {
+                    self.control(&name, control);
+                }
+            }
+            MprisMessage::Scroll(delta) => self.scroll(delta),
+            MprisMessage::Tick => self.players.broadcast(MprisPlayerMessage::Tick),
+        }
+    }
+}
+
+impl Mpris {
+    fn current_name(&self) -> Option<String> {
+        let playing = self.order.iter().find(|it| {
+            self.states
+                .get(*it)
+                .is_some_and(|it| it.status == PlaybackStatus::Playing)
+        });
+        playing
+            .or_else(|| self.order.iter().find(|it| self.states.contains_key(*it)))
+            .cloned()
+    }
+
+    fn current(&self) -> Option<&PlayerState> {
+        self.states.get(&self.current_name()?)
+    }
+
+    async fn add(&mut self, name: String, sender: AsyncComponentSender<Self>) -> zbus::Result<()> {
+        if self.proxies.contains_key(&name) {
+            return Ok(());
+        }
+        debug!("Media player added {name}");
+        let connection = crate::dbus::session().await?;
+        let player = PlayerProxy::builder(&connection)
+            .destination(name.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        self.proxies.insert(name.clone(), player);
+        self.order.push(name.clone());
+
+        let task_name = name.clone();
+        let task = relm4::spawn(async move {
+            if let Err(err) = watch_player(connection, task_name.clone(), sender).await {
+                error!("Error watching media player {task_name}: {err}");
+            }
+        });
+        self.tasks.insert(name, task);
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) {
+        debug!("Media player removed {name}");
+        if let Some(task) = self.tasks.remove(name) {
+            task.abort();
+        }
+        self.proxies.remove(name);
+        self.states.remove(name);
+        self.order.retain(|it| it != name);
+        let mut guard = self.players.guard();
+        if let Some(index) = guard.iter().position(|it| it.name == name) {
+            _ = guard.remove(index);
+        }
+    }
+
+    fn set_state(&mut self, name: String, state: PlayerState) {
+        if !self.proxies.contains_key(&name) {
+            return;
+        }
+        let was_playing = self
+            .states
+            .get(&name)
+            .is_some_and(|it| it.status == PlaybackStatus::Playing);
+        // Player that started last is the one shown in the bar
+        if state.status == PlaybackStatus::Playing && !was_playing {
+            self.order.retain(|it| *it != name);
+            self.order.insert(0, name.clone());
+        }
+        self.states.insert(name.clone(), state.clone());
+
+        let mut guard = self.players.guard();
+        match guard.iter().position(|it| it.name == name) {
+            Some(index) => guard.send(index, MprisPlayerMessage::Update(state)),
+            None => {
+                guard.push_back((name, state));
+            }
+        }
+    }
+
+    /// Positions only move while playing, so there's nothing to tick otherwise
+    fn update_tick(&mut self, sender: AsyncComponentSender<Self>) {
+        let playing = self
+            .states
+            .values()
+            .any(|it| it.status == PlaybackStatus::Playing);
+        match (playing, self.tick.is_some()) {
+            (true, false) => {
+                self.tick = Some(relm4::spawn(async move {
+                    let mut interval = tokio::time::interval(TICK);
+                    loop {
+                        interval.tick().await;
+                        sender.input(MprisMessage::Tick);
+                    }
+                }));
+            }
+            (false, true) => {
+                if let Some(tick) = self.tick.take() {
+                    tick.abort();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn control(&self, name: &str, control: PlayerControl) {
+        let Some(player) = self.proxies.get(name).cloned() else {
+            return;
+        };
+        let track_id = self.states.get(name).and_then(|it| it.track_id.clone());
+        let name = name.to_string();
+        relm4::spawn(async move {
+            let result = match control {
+                PlayerControl::PlayPause => player.play_pause().await,
+                PlayerControl::Next => player.next().await,
+                PlayerControl::Previous => player.previous().await,
+                PlayerControl::SetPosition(position) => match track_id {
+                    Some(track_id) => match ObjectPath::try_from(track_id.as_str()) {
+                        Ok(track_id) => player.set_position(&track_id, position).await,
+                        Err(err) => Err(err.into()),
+                    },
+                    None => Ok(()),
+                },
+                PlayerControl::Raise => raise(&player).await,
+            };
+            if let Err(err) = result {
+                error!("Error controlling media player {name}: {err}");
+            }
+        });
+    }
+
+    fn scroll(&mut self, delta: f64) {
+        let Some(name) = self.current_name() else {
+            return;
+        };
+        let (Some(player), Some(state)) =
+            (self.proxies.get(&name).cloned(), self.states.get_mut(&name))
+        else {
+            return;
+        };
+        // Scrolling up goes forward and louder
+        let steps = -delta;
+        let volume = state
+            .volume
+            .map(|it| (it + steps * self.volume_step).clamp(0f64, 1f64));
+        if matches!(self.scroll, MprisScroll::Volume) {
+            // Kept right away, so fast scrolling doesn't start from a stale value
+            state.volume = volume;
+        }
+        let can_seek = state.can_seek;
+        let seek_step = self.seek_step.as_micros() as f64;
+        let scroll = self.scroll;
+        relm4::spawn(async move {
+            let result = match (scroll, volume) {
+                (MprisScroll::Volume, Some(volume)) => player.set_volume(volume).await,
+                (MprisScroll::Seek, _) if can_seek => player.seek((steps * seek_step) as i64).await,
+                _ => Ok(()),
+            };
+            if let Err(err) = result {
+                error!("Error scrolling media player {name}: {err}");
+            }
+        });
+    }
+}
+
+impl PlayerState {
+    /// Reads everything at once, the player interface may be slow to answer
+    pub async fn fetch(properties: &PropertiesProxy<'_>, name: &str) -> zbus::Result<Self> {
+        let identity = properties
+            .get(
+                InterfaceName::from_static_str_unchecked("org.mpris.MediaPlayer2"),
+                "Identity",
+            )
+            .await
+            .ok()
+            .and_then(|it| String::try_from(it).ok())
+            .unwrap_or_else(|| name.trim_start_matches(MPRIS_PREFIX).to_string());
+        let player = properties
+            .get_all(InterfaceName::from_static_str_unchecked(MPRIS_PLAYER))
+            .await?;
+        Ok(Self::from_properties(identity, &player))
+    }
+
+    pub fn from_properties(identity: String, properties: &HashMap<String, OwnedValue>) -> Self {
+        let flag = |name: &str| {
+            properties
+                .get(name)
+                .and_then(|it| it.downcast_ref::<bool>())
+                .copied()
+                .unwrap_or(false)
+        };
+        let status = match properties
+            .get("PlaybackStatus")
+            .and_then(|it| it.downcast_ref::<str>())
+        {
+            Some("Playing") => PlaybackStatus::Playing,
+            Some("Paused") => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        };
+        let metadata = properties
+            .get("Metadata")
+            .and_then(|it| HashMap::<String, OwnedValue>::try_from(it.clone()).ok())
+            .unwrap_or_default();
+        let text = |name: &str| {
+            metadata
+                .get(name)
+                .and_then(|it| it.downcast_ref::<str>())
+                .filter(|it| !it.is_empty())
+                .map(str::to_string)
+        };
+        let artists = match metadata.get("xesam:artist") {
+            Some(value) => match value.downcast_ref::<str>() {
+                Some(artist) => vec![artist.to_string()],
+                None => Vec::<String>::try_from(value.clone()).unwrap_or_default(),
+            },
+            None => Vec::new(),
+        };
+        let track_id = metadata.get("mpris:trackid").and_then(|it| {
+            it.downcast_ref::<ObjectPath>()
+                .map(|it| it.to_string())
+                .or_else(|| it.downcast_ref::<str>().map(str::to_string))
+        });
+        let length = metadata.get("mpris:length").and_then(|it| {
+            it.downcast_ref::<i64>()
+                .copied()
+                .or_else(|| it.downcast_ref::<u64>().map(|it| *it as i64))
+        });
+        let art = text("mpris:artUrl")
+            .filter(|it| it.starts_with("file://"))
+            .and_then(|it| gio::File::for_uri(&it).path());
+
+        Self {
+            identity,
+            status,
+            track_id,
+            title: text("xesam:title"),
+            artists: artists.into_iter().filter(|it| !it.is_empty()).collect(),
+            album: text("xesam:album"),
+            art,
+            length: length.filter(|it| *it > 0),
+            position: properties
+                .get("Position")
+                .and_then(|it| it.downcast_ref::<i64>())
+                .copied()
+                .unwrap_or(0),
+            position_at: Instant::now(),
+            volume: properties
+                .get("Volume")
+                .and_then(|it| it.downcast_ref::<f64>())
+                .copied(),
+            can_go_next: flag("CanGoNext"),
+            can_go_previous: flag("CanGoPrevious"),
+            can_control: flag("CanControl"),
+            can_seek: flag("CanSeek"),
+        }
+    }
+
+    /// "Artist — Title", or the player name when nothing is known about the track
+    pub fn label(&self) -> String {
+        match (&self.title, self.artists.is_empty()) {
+            (Some(title), false) => format!("{} — {title}", self.artists.join(", ")),
+            (Some(title), true) => title.clone(),
+            (None, _) => self.identity.clone(),
+        }
+    }
+
+    pub fn position_now(&self) -> i64 {
+        let mut position = self.position;
+        if self.status == PlaybackStatus::Playing {
+            position += self.position_at.elapsed().as_micros() as i64;
+        }
+        match self.length {
+            Some(length) => position.min(length),
+            None => position,
+        }
+    }
+}
+
+pub fn play_pause_icon(status: Option<PlaybackStatus>) -> &'static str {
+    match status {
+        Some(PlaybackStatus::Playing) => "media-playback-pause-symbolic",
+        _ => "media-playback-start-symbolic",
+    }
+}
+
+/// "1:05" or "1:02:03"
+pub fn format_time(micros: i64) -> String {
+    let seconds = micros.max(0) / 1_000_000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+async fn raise(player: &PlayerProxy<'static>) -> zbus::Result<()> {
+    let proxy = player.inner();
+    MediaPlayer2Proxy::builder(proxy.connection())
+        .destination(proxy.destination().to_owned())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?
+        .raise()
+        .await
+}
+
+/// Reports players that are already running, then the ones that come and go
+async fn watch_players(sender: AsyncComponentSender<Mpris>) -> zbus::Result<()> {
+    let connection = crate::dbus::session().await?;
+    let dbus = DBusProxy::new(&connection).await?;
+    // Subscribed first, so no player is missed between listing and watching
+    let mut changes = dbus.receive_name_owner_changed().await?;
+    for name in dbus.list_names().await? {
+        if name.starts_with(MPRIS_PREFIX) {
+            sender.input(MprisMessage::Added(name.to_string()));
+        }
+    }
+    while let Some(signal) = changes.next().await {
+        let args = signal.args()?;
+        let name = args.name().to_string();
+        if !name.starts_with(MPRIS_PREFIX) {
+            continue;
+        }
+        if args.old_owner().is_some() {
+            sender.input(MprisMessage::Removed(name.clone()));
+        }
+        if args.new_owner().is_some() {
+            sender.input(MprisMessage::Added(name));
+        }
+    }
+    Ok(())
+}
+
+/// Sends the player state on start and after every change
+async fn watch_player(
+    connection: zbus::Connection,
+    name: String,
+    sender: AsyncComponentSender<Mpris>,
+) -> zbus::Result<()> {
+    let properties = PropertiesProxy::builder(&connection)
+        .destination(name.clone())?
+        .path(MPRIS_PATH)?
+        .build()
+        .await?;
+    let player = PlayerProxy::builder(&connection)
+        .destination(name.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let changed = properties.receive_properties_changed().await?.map(|_| ());
+    let seeked = player.receive_seeked().await?.map(|_| ());
+    let mut events = futures::stream::select(changed, seeked);
+    loop {
+        let state = PlayerState::fetch(&properties, &name).await?;
+        sender.input(MprisMessage::State(name.clone(), state));
+        if events.next().await.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_time, PlaybackStatus, PlayerState};
+    use crate::dbus::{PlayerProxy, MPRIS_PATH};
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use zbus::fdo::PropertiesProxy;
+    use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+    use zbus::{dbus_interface, CacheProperties, ConnectionBuilder, Guid};
+
+    const NAME: &str = "org.mpris.MediaPlayer2.mock";
+
+    struct MockRoot;
+
+    #[dbus_interface(name = "org.mpris.MediaPlayer2")]
+    impl MockRoot {
+        #[dbus_interface(property)]
+        fn identity(&self) -> String {
+            "Mock Player".to_string()
+        }
+    }
+
+    /// Stands in for a media player, only play/pause changes anything
+    struct MockPlayer {
+        playing: bool,
+    }
+
+    #[dbus_interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl MockPlayer {
+        fn play_pause(&mut self) {
+            self.playing = !self.playing;
+        }
+
+        #[dbus_interface(property)]
+        fn playback_status(&self) -> String {
+            if self.playing { "Playing" } else { "Paused" }.to_string()
+        }
+
+        #[dbus_interface(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let track_id = ObjectPath::try_from("/org/mpris/MediaPlayer2/track/1").unwrap();
+            HashMap::from([
+                ("mpris:trackid".to_string(), Value::from(track_id).into()),
+                (
+                    "mpris:length".to_string(),
+                    Value::from(180_000_000i64).into(),
+                ),
+                ("xesam:title".to_string(), Value::from("Song").into()),
+                (
+                    "xesam:artist".to_string(),
+                    Value::from(vec!["First", "Second"]).into(),
+                ),
+                (
+                    "mpris:artUrl".to_string(),
+                    Value::from("file:///tmp/cover%20art.png").into(),
+                ),
+            ])
+        }
+
+        #[dbus_interface(property)]
+        fn position(&self) -> i64 {
+            42_000_000
+        }
+
+        #[dbus_interface(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+    }
+
+    /// Private peer to peer bus with the mock player on the other side
+    async fn connect() -> (zbus::Connection, zbus::Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let server = ConnectionBuilder::unix_stream(server)
+            .server(&guid)
+            .p2p()
+            .serve_at(MPRIS_PATH, MockRoot)
+            .unwrap()
+            .serve_at(MPRIS_PATH, MockPlayer { playing: false })
+            .unwrap()
+            .build();
+        let client = ConnectionBuilder::unix_stream(client).p2p().build();
+        futures::try_join!(server, client).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_player_state() {
+        let (_server, client) = connect().await;
+        let properties = PropertiesProxy::builder(&client)
+            .destination(NAME)
+            .unwrap()
+            .path(MPRIS_PATH)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let state = PlayerState::fetch(&properties, NAME).await.unwrap();
+        assert_eq!(state.identity, "Mock Player");
+        assert_eq!(state.status, PlaybackStatus::Paused);
+        assert_eq!(state.label(), "First, Second — Song");
+        assert_eq!(
+            state.track_id.as_deref(),
+            Some("/org/mpris/MediaPlayer2/track/1")
+        );
+        assert_eq!(state.length, Some(180_000_000));
+        assert_eq!(state.position, 42_000_000);
+        assert_eq!(state.art, Some(PathBuf::from("/tmp/cover art.png")));
+        assert!(state.can_go_next);
+        assert!(!state.can_go_previous);
+        assert_eq!(state.volume, None);
+
+        let player = PlayerProxy::builder(&client)
+            .destination(NAME)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .unwrap();
+        player.play_pause().await.unwrap();
+        let state = PlayerState::fetch(&properties, NAME).await.unwrap();
+        assert_eq!(state.status, PlaybackStatus::Playing);
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "0:00");
+        assert_eq!(format_time(65_000_000), "1:05");
+        assert_eq!(format_time(3_723_000_000), "1:02:03");
+        assert_eq!(format_time(-1), "0:00");
+    }
+}
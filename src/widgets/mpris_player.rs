@@ -0,0 +1,229 @@
+use crate::widgets::mpris::{
+    format_time, play_pause_icon, PlaybackStatus, PlayerControl, PlayerState,
+};
+use gtk::glib::Propagation;
+use gtk::prelude::{BoxExt, ButtonExt, OrientableExt, RangeExt, WidgetExt};
+use relm4::factory::FactoryComponent;
+use relm4::{FactorySender, RelmWidgetExt};
+use std::time::Duration;
+
+/// Player card in the popover
+#[derive(Debug)]
+pub struct MprisPlayer {
+    pub name: String,
+    state: PlayerState,
+    /// Estimated from the last known position while playing
+    position: i64,
+    /// Slider is being dragged, the position is sent once it settles
+    seek: Option<u32>,
+    seek_generation: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum MprisPlayerMessage {
+    Update(PlayerState),
+    Tick,
+    Seek(i64),
+    /// Slider stayed still since the seek with this generation
+    SeekSettled(u32),
+}
+
+/// Size of album art in the popover
+const ART_SIZE: i32 = 64;
+/// Dragging the slider seeks once it stops for this long
+const SEEK_DELAY: Duration = Duration::from_millis(250);
+
+#[relm4::factory(pub)]
+impl FactoryComponent for MprisPlayer {
+    type Init = (String, PlayerState);
+    type Input = MprisPlayerMessage;
+    type Output = (String, PlayerControl);
+    type ParentWidget = gtk::Box;
+    type CommandOutput = ();
+
+    view! {
+        #[root]
+        gtk::Box {
+            add_css_class: "mpris__player",
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 4,
+            #[watch]
+            set_class_active: ("playing", self.state.status == PlaybackStatus::Playing),
+
+            gtk::Box {
+                set_spacing: 8,
+
+                // Art is loaded from disk, so it's only set again when the path changes
+                #[name = "art"]
+                gtk::Image {
+                    add_css_class: "mpris__art",
+                    set_pixel_size: ART_SIZE,
+                    #[watch]
+                    set_visible: self.state.art.is_some(),
+                    set_from_file: self.state.art.as_ref(),
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_valign: gtk::Align::Center,
+                    set_hexpand: true,
+
+                    gtk::Button {
+                        add_css_class: "mpris__identity",
+                        add_css_class: "flat",
+                        set_halign: gtk::Align::Start,
+                        #[watch]
+                        set_label: &self.state.identity,
+                        set_tooltip_text: Some("Show player"),
+                        connect_clicked[sender, name = self.name.clone()] => move |_| {
+                            _ = sender.output((name.clone(), PlayerControl::Raise));
+                        },
+                    },
+
+                    gtk::Label {
+                        add_css_class: "mpris__title",
+                        set_halign: gtk::Align::Start,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        set_max_width_chars: 40,
+                        #[watch]
+                        set_label: self.state.title.as_deref().unwrap_or_default(),
+                    },
+
+                    gtk::Label {
+                        add_css_class: "mpris__artist",
+                        set_halign: gtk::Align::Start,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        set_max_width_chars: 40,
+                        #[watch]
+                        set_label: &self.state.artists.join(", "),
+                        #[watch]
+                        set_visible: !self.state.artists.is_empty(),
+                    },
+                },
+            },
+
+            gtk::Box {
+                set_spacing: 8,
+                #[watch]
+                set_visible: self.state.length.is_some(),
+
+                gtk::Scale {
+                    set_hexpand: true,
+                    set_draw_value: false,
+                    #[watch]
+                    set_sensitive: self.state.can_seek && self.state.track_id.is_some(),
+                    #[watch]
+                    set_range: (0f64, self.state.length.unwrap_or(1) as f64),
+                    #[watch]
+                    set_value: self.position as f64,
+                    connect_change_value[sender] => move |_, _, value| {
+                        sender.input(MprisPlayerMessage::Seek(value as i64));
+                        Propagation::Proceed
+                    },
+                },
+
+                gtk::Label {
+                    add_css_class: "mpris__time",
+                    #[watch]
+                    set_label: &format!(
+                        "{} / {}",
+                        format_time(self.position),
+                        format_time(self.state.length.unwrap_or(0)),
+                    ),
+                },
+            },
+
+            gtk::Box {
+                set_halign: gtk::Align::Center,
+                set_spacing: 4,
+
+                gtk::Button {
+                    add_css_class: "mpris__button",
+                    set_icon_name: "media-skip-backward-symbolic",
+                    #[watch]
+                    set_sensitive: self.state.can_go_previous,
+                    connect_clicked[sender, name = self.name.clone()] => move |_| {
+                        _ = sender.output((name.clone(), PlayerControl::Previous));
+                    },
+                },
+
+                gtk::Button {
+                    add_css_class: "mpris__button",
+                    #[watch]
+                    set_icon_name: play_pause_icon(Some(self.state.status)),
+                    #[watch]
+                    set_sensitive: self.state.can_control,
+                    connect_clicked[sender, name = self.name.clone()] => move |_| {
+                        _ = sender.output((name.clone(), PlayerControl::PlayPause));
+                    },
+                },
+
+                gtk::Button {
+                    add_css_class: "mpris__button",
+                    set_icon_name: "media-skip-forward-symbolic",
+                    #[watch]
+                    set_sensitive: self.state.can_go_next,
+                    connect_clicked[sender, name = self.name.clone()] => move |_| {
+                        _ = sender.output((name.clone(), PlayerControl::Next));
+                    },
+                },
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
+        let (name, state) = init;
+        Self {
+            name,
+            position: state.position_now(),
+            state,
+            seek: None,
+            seek_generation: 0,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: FactorySender<Self>,
+    ) {
+        let art = self.state.art.clone();
+        self.update(message, sender.clone());
+        if self.state.art != art {
+            widgets.art.set_from_file(self.state.art.as_ref());
+        }
+        self.update_view(widgets, sender);
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            MprisPlayerMessage::Update(state) => self.state = state,
+            MprisPlayerMessage::Tick => {}
+            MprisPlayerMessage::Seek(position) => {
+                self.seek_generation = self.seek_generation.wrapping_add(1);
+                self.seek = Some(self.seek_generation);
+                self.position = position;
+                let generation = self.seek_generation;
+                relm4::spawn(async move {
+                    tokio::time::sleep(SEEK_DELAY).await;
+                    sender.input(MprisPlayerMessage::SeekSettled(generation));
+                });
+                return;
+            }
+            MprisPlayerMessage::SeekSettled(generation) => {
+                if self.seek != Some(generation) {
+                    return;
+                }
+                self.seek = None;
+                let control = PlayerControl::SetPosition(self.position);
+                _ = sender.output((self.name.clone(), control));
+                return;
+            }
+        }
+        // Slider stays where it was dragged until the position is sent
+        if self.seek.is_none() {
+            self.position = self.state.position_now();
+        }
+    }
+}
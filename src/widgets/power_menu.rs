@@ -1,17 +1,163 @@
+use crate::dbus::{Login1ManagerProxy, Login1SessionProxy};
 use crate::ext::ParentFor;
-use crate::new_stateless_value_action;
 use gtk::prelude::*;
 use log::{error, info};
-use relm4::actions::{RelmAction, RelmActionGroup};
-use relm4::{ComponentParts, ComponentSender, SimpleComponent};
+use relm4::{Component, ComponentParts, ComponentSender, RelmWidgetExt};
+use serde::Deserialize;
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
-pub struct PowerMenu;
+pub struct PowerMenu {
+    entries: Vec<PowerEntry>,
+    confirm_timeout: u32,
+    /// Entry waiting for confirmation and seconds left until it runs
+    pending: Option<(usize, u32)>,
+    countdown: Option<tokio::task::JoinHandle<()>>,
+    error: Option<String>,
+    buttons: Vec<gtk::Button>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PowerMenuInit {
+    pub entries: Vec<PowerEntry>,
+    /// Seconds before a confirmation accepts itself, 0 waits for the user
+    pub confirm_timeout: u32,
+}
+
+impl Default for PowerMenuInit {
+    fn default() -> Self {
+        Self {
+            entries: vec![
+                PowerEntry::with_action(
+                    "Shutdown",
+                    "system-shutdown-symbolic",
+                    PowerAction::PowerOff,
+                ),
+                PowerEntry::with_action("Reboot", "system-reboot-symbolic", PowerAction::Reboot),
+                PowerEntry::with_action(
+                    "Suspend",
+                    "weather-clear-night-symbolic",
+                    PowerAction::Suspend,
+                ),
+                PowerEntry::with_command(
+                    "Screen off",
+                    "video-display-symbolic",
+                    &["hyprctl", "dispatch", "dpms", "off"],
+                ),
+                // logind only asks a locker to lock, hyprlock works without one running
+                PowerEntry::with_command(
+                    "Lock",
+                    "system-lock-screen-symbolic",
+                    &["hyprlock", "--immediate"],
+                ),
+            ],
+            confirm_timeout: 10,
+        }
+    }
+}
+
+/// Menu entry, runs either `command` or a built-in `action`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PowerEntry {
+    pub label: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Program and its arguments, not passed through a shell
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub action: Option<PowerAction>,
+    /// Ask before running. By default only built-in actions that end the session ask
+    #[serde(default)]
+    pub confirm: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    PowerOff,
+    Reboot,
+    Suspend,
+    Hibernate,
+    HybridSleep,
+    /// Signals the session to lock, needs hypridle or another listener
+    Lock,
+    Logout,
+}
+
+impl PowerAction {
+    /// Actions logind has no method for
+    fn command(&self) -> Option<&'static [&'static str]> {
+        match self {
+            PowerAction::Logout => Some(&["hyprctl", "dispatch", "exit"]),
+            _ => None,
+        }
+    }
+
+    fn is_destructive(&self) -> bool {
+        matches!(
+            self,
+            PowerAction::PowerOff | PowerAction::Reboot | PowerAction::Logout
+        )
+    }
+}
+
+impl PowerEntry {
+    fn with_action(label: &str, icon: &str, action: PowerAction) -> Self {
+        Self {
+            label: label.to_string(),
+            icon: Some(icon.to_string()),
+            command: Vec::new(),
+            action: Some(action),
+            confirm: None,
+        }
+    }
+
+    fn with_command(label: &str, icon: &str, command: &[&str]) -> Self {
+        Self {
+            label: label.to_string(),
+            icon: Some(icon.to_string()),
+            command: command.iter().map(|it| it.to_string()).collect(),
+            action: None,
+            confirm: None,
+        }
+    }
+
+    fn needs_confirm(&self) -> bool {
+        self.confirm
+            .unwrap_or_else(|| self.action.is_some_and(|it| it.is_destructive()))
+    }
+
+    /// Command takes precedence over action, so a built-in can be overridden
+    fn argv(&self) -> Option<Vec<String>> {
+        if !self.command.is_empty() {
+            return Some(self.command.clone());
+        }
+        self.action
+            .and_then(|it| it.command())
+            .map(|it| it.iter().map(|it| it.to_string()).collect())
+    }
+}
+
+#[derive(Debug)]
+pub enum PowerMenuMessage {
+    Select(usize),
+    /// One second of the confirmation countdown passed
+    CountdownTick,
+    Confirm,
+    Cancel,
+    Failed(String),
+    /// Whether each entry can run, from logind `Can*` methods
+    Availability(Vec<bool>),
+}
 
 #[relm4::component(pub)]
-impl SimpleComponent for PowerMenu {
-    type Init = ();
-    type Input = ();
+impl Component for PowerMenu {
+    type Init = PowerMenuInit;
+    type Input = PowerMenuMessage;
     type Output = ();
+    type CommandOutput = ();
 
     view! {
         #[root]
@@ -20,54 +166,366 @@ impl SimpleComponent for PowerMenu {
             add_css_class: "hoverable",
             add_css_class: "power-menu",
             set_icon_name: "system-shutdown",
-            set_parent_for: popover = &gtk::PopoverMenu::from_model(Some(&power_menu)),
+            #[watch]
+            set_class_active: ("error", model.error.is_some()),
+            #[watch]
+            set_tooltip_text: model.error.as_deref(),
+            set_parent_for: popover = &gtk::Popover {
+                add_css_class: "menu",
+                connect_closed => PowerMenuMessage::Cancel,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+
+                    #[name = "entries_box"]
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        #[watch]
+                        set_visible: model.pending.is_none(),
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 8,
+                        #[watch]
+                        set_visible: model.pending.is_some(),
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.confirm_text(),
+                        },
+
+                        gtk::Box {
+                            set_spacing: 8,
+                            set_homogeneous: true,
+
+                            gtk::Button {
+                                set_label: "Cancel",
+                                connect_clicked => PowerMenuMessage::Cancel,
+                            },
+                            gtk::Button {
+                                #[watch]
+                                set_label: &model.confirm_label(),
+                                add_css_class: "destructive-action",
+                                connect_clicked => PowerMenuMessage::Confirm,
+                            },
+                        },
+                    },
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: model.error.as_deref().unwrap_or_default(),
+                        #[watch]
+                        set_visible: model.error.is_some(),
+                        set_wrap: true,
+                        set_max_width_chars: 40,
+                        add_css_class: "power-menu__error",
+                    },
+                },
+            },
             connect_clicked[popover] => move |_btn| {
                 popover.popup();
             }
         }
     }
 
-    menu! {
-        power_menu: {
-            "Shutdown" => Exec("systemctl shutdown".into()),
-            "Reboot" => Exec("systemctl reboot".into()),
-            "Sleep" => Exec("hyprctl dispatch dpms off".into()),
-            "Lock" => Exec("hyprlock --immediate".into()),
-        }
-    }
-
     fn init(
-        _init: Self::Init,
+        init: Self::Init,
         root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = Self;
+        let mut model = Self {
+            entries: init.entries,
+            confirm_timeout: init.confirm_timeout,
+            pending: None,
+            countdown: None,
+            error: None,
+            buttons: Vec::new(),
+        };
         let widgets = view_output!();
 
-        let action: RelmAction<Exec> =
-            RelmAction::new_with_target_value(|_action, value: String| {
-                info!("{:?}", value);
-                match std::process::Command::new("bash")
-                    .arg("-c")
-                    .arg(value.clone())
-                    .spawn()
-                {
-                    Ok(_std) => {
-                        info!("Successfully run command: {:?}", value);
-                    }
-                    Err(err) => {
-                        error!("Error running command: {:?}\n{}", value, err);
-                    }
-                };
-            });
+        for (index, entry) in model.entries.iter().enumerate() {
+            let content = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            if let Some(icon) = &entry.icon {
+                content.append(&gtk::Image::from_icon_name(icon));
+            }
+            content.append(&gtk::Label::new(Some(&entry.label)));
+            let button = gtk::Button::builder()
+                .child(&content)
+                .css_classes(["flat", "power-menu__entry"])
+                .build();
+            let sender = sender.clone();
+            button.connect_clicked(move |_| sender.input(PowerMenuMessage::Select(index)));
+            widgets.entries_box.append(&button);
+            model.buttons.push(button);
+        }
 
-        let mut group = RelmActionGroup::<ShellActionGroup>::new();
-        group.add_action(action);
-        group.register_for_widget(&widgets.popover);
+        let entries = model.entries.clone();
+        relm4::spawn(async move {
+            let availability = match zbus::Connection::system().await {
+                Ok(connection) => availability(&connection, &entries).await,
+                Err(err) => {
+                    error!("Error connecting to system bus: {err}");
+                    entries.iter().map(|it| it.argv().is_some()).collect()
+                }
+            };
+            sender.input(PowerMenuMessage::Availability(availability));
+        });
 
         ComponentParts { model, widgets }
     }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            PowerMenuMessage::Select(index) => {
+                self.error = None;
+                let Some(entry) = self.entries.get(index) else {
+                    return;
+                };
+                if entry.needs_confirm() {
+                    self.pending = Some((index, self.confirm_timeout));
+                    self.start_countdown(sender);
+                } else {
+                    widgets.popover.popdown();
+                    self.run(index, sender);
+                }
+            }
+            PowerMenuMessage::CountdownTick => {
+                if let Some((index, seconds)) = self.pending {
+                    if seconds <= 1 {
+                        sender.input(PowerMenuMessage::Confirm);
+                    } else {
+                        self.pending = Some((index, seconds - 1));
+                    }
+                }
+            }
+            PowerMenuMessage::Confirm => {
+                self.stop_countdown();
+                if let Some((index, _)) = self.pending.take() {
+                    widgets.popover.popdown();
+                    self.run(index, sender.clone());
+                }
+            }
+            PowerMenuMessage::Cancel => {
+                self.stop_countdown();
+                self.pending = None;
+            }
+            PowerMenuMessage::Failed(message) => {
+                self.error = Some(message);
+            }
+            PowerMenuMessage::Availability(availability) => {
+                for (button, available) in self.buttons.iter().zip(availability) {
+                    button.set_sensitive(available);
+                }
+            }
+        }
+        self.update_view(widgets, sender);
+    }
 }
 
-relm4::new_action_group!(ShellActionGroup, "power");
-new_stateless_value_action!(Exec, ShellActionGroup, "exec", String);
+impl PowerMenu {
+    fn confirm_text(&self) -> String {
+        self.pending
+            .and_then(|(index, _)| self.entries.get(index))
+            .map(|entry| format!("{}?", entry.label))
+            .unwrap_or_default()
+    }
+
+    fn confirm_label(&self) -> String {
+        match self.pending {
+            Some((_, seconds)) if self.confirm_timeout > 0 => format!("Confirm ({seconds})"),
+            _ => "Confirm".to_string(),
+        }
+    }
+
+    fn start_countdown(&mut self, sender: ComponentSender<Self>) {
+        self.stop_countdown();
+        if self.confirm_timeout == 0 {
+            return;
+        }
+        self.countdown = Some(relm4::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                sender.input(PowerMenuMessage::CountdownTick);
+            }
+        }));
+    }
+
+    fn stop_countdown(&mut self) {
+        if let Some(countdown) = self.countdown.take() {
+            countdown.abort();
+        }
+    }
+
+    /// Runs the entry in background and reports a failed start or exit status
+    fn run(&self, index: usize, sender: ComponentSender<Self>) {
+        let Some(entry) = self.entries.get(index) else {
+            return;
+        };
+        let label = entry.label.clone();
+        let argv = entry.argv();
+        let action = entry.action;
+        relm4::spawn(async move {
+            let result = match (argv, action) {
+                (Some(argv), _) => relm4::spawn_blocking(move || run_command(&argv))
+                    .await
+                    .unwrap_or_else(|err| Err(err.to_string())),
+                (None, Some(action)) => run_login1(action).await.map_err(|err| err.to_string()),
+                (None, None) => Err("No command configured".to_string()),
+            };
+            let Err(error) = result else {
+                return;
+            };
+            error!("Error running {label:?}: {error}");
+            sender.input(PowerMenuMessage::Failed(format!("{label}: {error}")));
+        });
+    }
+}
+
+/// Entries are available unless logind reports it can't do their action
+async fn availability(connection: &zbus::Connection, entries: &[PowerEntry]) -> Vec<bool> {
+    let mut result = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let available = match (entry.argv(), entry.action) {
+            (Some(_), _) => true,
+            (None, Some(action)) => can_run(connection, action).await.unwrap_or_else(|err| {
+                error!("Error checking if {:?} can run: {err}", entry.label);
+                false
+            }),
+            (None, None) => false,
+        };
+        result.push(available);
+    }
+    result
+}
+
+/// "challenge" means polkit will ask for a password, which is fine
+async fn can_run(connection: &zbus::Connection, action: PowerAction) -> zbus::Result<bool> {
+    let manager = Login1ManagerProxy::new(connection).await?;
+    let answer = match action {
+        PowerAction::PowerOff => manager.can_power_off().await?,
+        PowerAction::Reboot => manager.can_reboot().await?,
+        PowerAction::Suspend => manager.can_suspend().await?,
+        PowerAction::Hibernate => manager.can_hibernate().await?,
+        PowerAction::HybridSleep => manager.can_hybrid_sleep().await?,
+        PowerAction::Lock | PowerAction::Logout => return Ok(true),
+    };
+    Ok(answer == "yes" || answer == "challenge")
+}
+
+async fn run_login1(action: PowerAction) -> zbus::Result<()> {
+    let connection = zbus::Connection::system().await?;
+    call_login1(&connection, action).await
+}
+
+async fn call_login1(connection: &zbus::Connection, action: PowerAction) -> zbus::Result<()> {
+    info!("Calling logind {action:?}");
+    let manager = Login1ManagerProxy::new(connection).await?;
+    match action {
+        PowerAction::PowerOff => manager.power_off(true).await,
+        PowerAction::Reboot => manager.reboot(true).await,
+        PowerAction::Suspend => manager.suspend(true).await,
+        PowerAction::Hibernate => manager.hibernate(true).await,
+        PowerAction::HybridSleep => manager.hybrid_sleep(true).await,
+        PowerAction::Lock => Login1SessionProxy::new(connection).await?.lock().await,
+        PowerAction::Logout => Err(zbus::Error::Unsupported),
+    }
+}
+
+fn run_command(argv: &[String]) -> Result<(), String> {
+    let Some((program, args)) = argv.split_first() else {
+        return Err("No command configured".to_string());
+    };
+    info!("Running {argv:?}");
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| format!("Can't run {program}: {err}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().rev().find(|it| !it.trim().is_empty()) {
+        Some(line) => Err(line.trim().to_string()),
+        None => Err(format!("{program} exited with {}", output.status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{call_login1, can_run, PowerAction};
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex};
+    use zbus::{dbus_interface, ConnectionBuilder, Guid};
+
+    /// Stands in for logind, records called methods
+    struct MockManager {
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn can_power_off(&self) -> String {
+            "yes".to_string()
+        }
+
+        fn can_reboot(&self) -> String {
+            "challenge".to_string()
+        }
+
+        fn can_suspend(&self) -> String {
+            "no".to_string()
+        }
+
+        fn can_hibernate(&self) -> String {
+            "na".to_string()
+        }
+
+        fn power_off(&self, _interactive: bool) {
+            self.calls.lock().unwrap().push("PowerOff");
+        }
+    }
+
+    /// Private peer to peer bus with the mock on the other side
+    async fn connect(calls: Arc<Mutex<Vec<&'static str>>>) -> (zbus::Connection, zbus::Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let server = ConnectionBuilder::unix_stream(server)
+            .server(&guid)
+            .p2p()
+            .serve_at("/org/freedesktop/login1", MockManager { calls })
+            .unwrap()
+            .build();
+        let client = ConnectionBuilder::unix_stream(client).p2p().build();
+        futures::try_join!(server, client).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login1_backend() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (_server, client) = connect(calls.clone()).await;
+
+        assert!(can_run(&client, PowerAction::PowerOff).await.unwrap());
+        assert!(can_run(&client, PowerAction::Reboot).await.unwrap());
+        assert!(!can_run(&client, PowerAction::Suspend).await.unwrap());
+        assert!(!can_run(&client, PowerAction::Hibernate).await.unwrap());
+        assert!(can_run(&client, PowerAction::Lock).await.unwrap());
+        // Mock has no such method, so the call fails
+        assert!(can_run(&client, PowerAction::HybridSleep).await.is_err());
+
+        call_login1(&client, PowerAction::PowerOff).await.unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec!["PowerOff"]);
+    }
+}
@@ -1,29 +1,96 @@
-use crate::widgets::tray_item::TrayItem;
-use gtk::prelude::{OrientableExt, WidgetExt};
-use log::debug;
+use crate::widgets::tray_item::{TrayItem, TrayItemMessage};
+use gtk::prelude::{BoxExt, ButtonExt, OrientableExt, WidgetExt};
+use log::{debug, error, info};
 use relm4::component::AsyncComponentParts;
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::SimpleAsyncComponent;
-use relm4::AsyncComponentSender;
+use relm4::{AsyncComponentSender, RelmWidgetExt};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use system_tray::client::{Client, Event, UpdateEvent};
 use system_tray::item::StatusNotifierItem;
 use system_tray::menu::TrayMenu;
 
 type State = HashMap<String, (StatusNotifierItem, Option<TrayMenu>)>;
 
+/// Delay between attempts to start the tray while another host holds it
+const RETRY: Duration = Duration::from_secs(10);
+
 pub struct SysTray {
-    client: Rc<Client>,
-    items: Arc<Mutex<State>>,
+    /// `None` until the tray host starts
+    client: Option<Rc<Client>>,
     tray_items: FactoryVecDeque<TrayItem>,
+    drawer_items: FactoryVecDeque<TrayItem>,
+    drawer_open: bool,
+    rules: Vec<TrayRule>,
+    hide_passive: bool,
+    /// Where each known item is shown and its sort order
+    placements: HashMap<String, (TrayPlacement, i32)>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SysTrayInit {
+    /// First matching rule decides where an item goes
+    pub rules: Vec<TrayRule>,
+    /// Hides items while they report the passive status
+    pub hide_passive: bool,
+}
+
+/// Every set field is a glob with `*` and `?` and has to match
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TrayRule {
+    /// Item id, e.g. "nm-applet" or "chrome_status_icon_*"
+    pub id: Option<String>,
+    pub title: Option<String>,
+    /// "application_status", "communications", "system_services" or "hardware"
+    pub category: Option<String>,
+    pub placement: TrayPlacement,
+    /// Items are sorted by it, items without a rule have 0
+    pub order: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayPlacement {
+    #[default]
+    Show,
+    Hide,
+    /// Behind the chevron button
+    Drawer,
+}
+
+impl TrayRule {
+    fn matches(&self, item: &StatusNotifierItem, title: Option<&str>) -> bool {
+        let category = snake_case(&format!("{:?}", item.category));
+        let fields = [
+            (&self.id, Some(item.id.as_str())),
+            (&self.title, title),
+            (&self.category, Some(category.as_str())),
+        ];
+        fields.iter().all(|(pattern, value)| match pattern {
+            None => true,
+            Some(pattern) => value.is_some_and(|it| glob_match(pattern, it)),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum SysTrayMessage {
+    /// Tries to start the tray host, retried until it succeeds
+    Connect,
+    Tray(Event),
+    ToggleDrawer,
 }
 
 #[relm4::component(pub async)]
 impl SimpleAsyncComponent for SysTray {
-    type Init = ();
-    type Input = Event;
+    type Init = SysTrayInit;
+    type Input = SysTrayMessage;
     type Output = ();
 
     view! {
@@ -32,83 +99,296 @@ impl SimpleAsyncComponent for SysTray {
             add_css_class: "module",
             add_css_class: "systray",
             set_orientation: gtk::Orientation::Horizontal,
+            #[watch]
+            set_class_active: ("error", model.error.is_some()),
+
+            gtk::Image {
+                set_icon_name: Some("dialog-warning-symbolic"),
+                add_css_class: "systray__error",
+                #[watch]
+                set_visible: model.error.is_some(),
+                #[watch]
+                set_tooltip_text: model.error.as_deref(),
+            },
+
+            #[local_ref]
+            tray_box -> gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+            },
+
+            gtk::Revealer {
+                set_transition_type: gtk::RevealerTransitionType::SlideLeft,
+                #[watch]
+                set_reveal_child: model.drawer_open,
+
+                #[local_ref]
+                drawer_box -> gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    add_css_class: "systray__drawer",
+                },
+            },
+
+            gtk::Button {
+                add_css_class: "systray__chevron",
+                #[watch]
+                set_visible: !model.drawer_items.is_empty(),
+                #[watch]
+                set_icon_name: model.chevron_icon(),
+                connect_clicked => SysTrayMessage::ToggleDrawer,
+            },
         }
     }
 
     async fn init(
-        _init: Self::Init,
+        init: Self::Init,
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
-        let client = Client::new("ru.deltadelete.rgb")
-            .await
-            .expect("Another program is serving system tray!");
-        let client = Rc::new(client);
-        let mut tray_rx = client.subscribe();
+        sender.input(SysTrayMessage::Connect);
+
+        let model = Self {
+            client: None,
+            tray_items: FactoryVecDeque::builder()
+                .launch(gtk::Box::default())
+                .detach(),
+            drawer_items: FactoryVecDeque::builder()
+                .launch(gtk::Box::default())
+                .detach(),
+            drawer_open: false,
+            rules: init.rules,
+            hide_passive: init.hide_passive,
+            placements: HashMap::new(),
+            error: None,
+        };
+        let tray_box = model.tray_items.widget();
+        let drawer_box = model.drawer_items.widget();
+        let widgets = view_output!();
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
+        let event = match message {
+            SysTrayMessage::Connect => {
+                self.connect(sender).await;
+                return;
+            }
+            SysTrayMessage::ToggleDrawer => {
+                self.drawer_open = !self.drawer_open;
+                return;
+            }
+            SysTrayMessage::Tray(event) => event,
+        };
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        match event {
+            Event::Add(id, _item) => {
+                debug!("Tray item added {}", id);
+                let items = client.items();
+                let items = items.lock().unwrap();
+                let Some(item) = items.get(&id) else {
+                    return;
+                };
+                let title = item.0.title.clone();
+                self.insert(id, item.clone(), title.as_deref(), &client);
+            }
+            Event::Update(id, update_event) => {
+                debug!("Tray item updated {}", id);
+                // Title rules are checked again, so an item may move
+                if let UpdateEvent::Title(title) = &update_event {
+                    let items = client.items();
+                    let items = items.lock().unwrap();
+                    if let Some(item) = items.get(&id) {
+                        let placement = self.placement(&item.0, title.as_deref());
+                        if self.placements.get(&id) != Some(&placement) {
+                            self.remove(&id);
+                            self.insert(id.clone(), item.clone(), title.as_deref(), &client);
+                        }
+                    }
+                }
+                for factory in [&mut self.tray_items, &mut self.drawer_items] {
+                    let guard = factory.guard();
+                    if let Some(index) = guard.iter().position(|it| it.key == id) {
+                        guard.send(index, TrayItemMessage::Update(update_event));
+                        break;
+                    }
+                }
+            }
+            Event::Remove(id) => {
+                debug!("Tray item removed {}", id);
+                self.remove(&id);
+            }
+        }
+    }
+}
+
+impl SysTray {
+    /// Starts the tray host. Fails while another bar owns the watcher name,
+    /// in that case the error is shown in place of the tray and the start is retried.
+    async fn connect(&mut self, sender: AsyncComponentSender<Self>) {
+        let client = match Client::new("ru.deltadelete.rgb").await {
+            Ok(client) => Rc::new(client),
+            Err(err) => {
+                error!("Error starting system tray, retrying in {RETRY:?}: {err}");
+                self.error = Some(format!("System tray is unavailable: {err}"));
+                relm4::spawn(async move {
+                    tokio::time::sleep(RETRY).await;
+                    sender.input(SysTrayMessage::Connect);
+                });
+                return;
+            }
+        };
+        info!("System tray started");
+        self.error = None;
 
+        let mut tray_rx = client.subscribe();
         let sender_clone = sender.clone();
         relm4::spawn(async move {
             while let Ok(ev) = tray_rx.recv().await {
-                sender_clone.input(ev);
+                sender_clone.input(SysTrayMessage::Tray(ev));
             }
         });
 
         let items = client.items();
-        let sender_clone = sender.clone();
         if let Ok(guard) = items.lock() {
             let map: State = guard.clone();
             map.iter().for_each(|x| {
                 let id = x.0.clone();
                 let tuple = x.1;
                 let item = Box::new(tuple.0.clone());
-                sender_clone.input(Event::Add(id.clone(), item));
+                sender.input(SysTrayMessage::Tray(Event::Add(id.clone(), item)));
                 if let Some(menu) = tuple.1.clone() {
-                    sender_clone.input(Event::Update(id, UpdateEvent::Menu(menu)));
+                    sender.input(SysTrayMessage::Tray(Event::Update(
+                        id,
+                        UpdateEvent::Menu(menu),
+                    )));
                 }
             })
         }
+        self.client = Some(client);
+    }
 
-        let tray_items = FactoryVecDeque::<TrayItem>::builder()
-            .launch(root.clone())
-            .detach();
+    fn chevron_icon(&self) -> &'static str {
+        if self.drawer_open {
+            "pan-end-symbolic"
+        } else {
+            "pan-start-symbolic"
+        }
+    }
 
-        let model = Self {
-            client,
-            items,
-            tray_items,
+    fn placement(&self, item: &StatusNotifierItem, title: Option<&str>) -> (TrayPlacement, i32) {
+        self.rules
+            .iter()
+            .find(|it| it.matches(item, title))
+            .map(|it| (it.placement, it.order.unwrap_or(0)))
+            .unwrap_or((TrayPlacement::Show, 0))
+    }
+
+    /// Inserts the item before the first one with a greater order
+    fn insert(
+        &mut self,
+        id: String,
+        item: (StatusNotifierItem, Option<TrayMenu>),
+        title: Option<&str>,
+        client: &Rc<Client>,
+    ) {
+        // Items added between subscribing and reading the snapshot come twice
+        if self.placements.contains_key(&id) {
+            debug!("Tray item {id} is already shown");
+            return;
+        }
+        let (placement, order) = self.placement(&item.0, title);
+        self.placements.insert(id.clone(), (placement, order));
+        let factory = match placement {
+            TrayPlacement::Show => &mut self.tray_items,
+            TrayPlacement::Drawer => &mut self.drawer_items,
+            TrayPlacement::Hide => {
+                debug!("Tray item {id} is hidden");
+                return;
+            }
         };
-        let root = model.tray_items.widget();
-        let widgets = view_output!();
-        AsyncComponentParts { model, widgets }
+        let mut guard = factory.guard();
+        let index = guard
+            .iter()
+            .position(|it| self.placements.get(&it.key).is_some_and(|it| it.1 > order))
+            .unwrap_or(guard.len());
+        guard.insert(index, (id, item, client.clone(), self.hide_passive));
     }
 
-    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
-        match message {
-            Event::Add(id, _item) => {
-                debug!("Tray item added {}", id);
-                let mut guard = self.tray_items.guard();
-                let items = self.items.lock().unwrap();
-                let id_clone = id.clone();
-                let item = items.get(&id_clone).unwrap();
-                let tray = item.clone();
-                guard.push_back((id, tray, self.client.clone()));
+    fn remove(&mut self, id: &str) {
+        self.placements.remove(id);
+        for factory in [&mut self.tray_items, &mut self.drawer_items] {
+            let mut guard = factory.guard();
+            if let Some(index) = guard.iter().position(|it| it.key == id) {
+                _ = guard.remove(index);
             }
-            Event::Update(id, update_event) => {
-                debug!("Tray item updated {}", id);
-                let guard = self.tray_items.guard();
-                let item = guard.iter().position(|it| it.key == id);
-                if let Some(option) = item {
-                    guard.send(option, update_event);
-                }
+        }
+        if self.drawer_items.is_empty() {
+            self.drawer_open = false;
+        }
+    }
+}
+
+/// "ApplicationStatus" to "application_status"
+fn snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            result.push('_');
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+/// Matches `*` as any run of characters and `?` as one character, ignoring case
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<char>>();
+    let text = text.to_lowercase().chars().collect::<Vec<char>>();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
             }
-            Event::Remove(id) => {
-                debug!("Tray item removed {}", id);
-                let mut guard = self.tray_items.guard();
-                let some_index = guard.iter().position(|it| it.key == id);
-                if let Some(index) = some_index {
-                    _ = guard.remove(index);
-                }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
             }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
         }
     }
+    pattern[p..].iter().all(|it| *it == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, snake_case};
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("nm-applet", "nm-applet"));
+        assert!(glob_match("chrome_status_icon_*", "chrome_status_icon_1"));
+        assert!(glob_match("*steam*", "Steam"));
+        assert!(glob_match("?lack", "slack"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("nm-*", "blueman"));
+        assert!(!glob_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("ApplicationStatus"), "application_status");
+        assert_eq!(snake_case("Hardware"), "hardware");
+    }
 }
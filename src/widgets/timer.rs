@@ -0,0 +1,614 @@
+use crate::ext::ParentFor;
+use crate::new_stateless_value_action;
+use chrono::Utc;
+use gtk::glib::Propagation;
+use gtk::prelude::{ApplicationExt, BoxExt, GestureSingleExt, PopoverExt, ToVariant, WidgetExt};
+use gtk::{gdk, gio, glib, EventControllerScrollFlags};
+use log::{debug, error, info};
+use relm4::actions::{RelmAction, RelmActionGroup};
+use relm4::component::AsyncComponentParts;
+use relm4::component::SimpleAsyncComponent;
+use relm4::AsyncComponentSender;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest countdown that can be set by scrolling
+const MAX_DURATION: u64 = 24 * 60 * 60;
+/// Shortest configured or scrolled period, a zero one would finish on every tick
+const MIN_PERIOD: u64 = 60;
+
+pub struct Timer {
+    state: TimerState,
+    presets: Vec<u32>,
+    step: u64,
+    work: u64,
+    short_break: u64,
+    long_break: u64,
+    long_break_every: u32,
+    menu: gio::Menu,
+    label: String,
+    /// `progress-N` class, N is the finished part of the countdown rounded down to tens of percent
+    progress: Option<String>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TimerInit {
+    /// Countdown lengths in the menu, in minutes
+    pub presets: Vec<u32>,
+    /// Countdown change per scroll step, in seconds
+    pub step: u64,
+    /// Pomodoro work period, in minutes
+    pub work: u64,
+    /// Pomodoro short break, in minutes
+    pub short_break: u64,
+    /// Pomodoro long break, in minutes
+    pub long_break: u64,
+    /// Long break comes after this many work periods
+    pub long_break_every: u32,
+}
+
+impl Default for TimerInit {
+    fn default() -> Self {
+        Self {
+            presets: vec![5, 10, 15, 30, 60],
+            step: 60,
+            work: 25,
+            short_break: 5,
+            long_break: 15,
+            long_break_every: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerMode {
+    #[default]
+    Timer,
+    Stopwatch,
+    Pomodoro,
+}
+
+impl TimerMode {
+    const ALL: [TimerMode; 3] = [TimerMode::Timer, TimerMode::Stopwatch, TimerMode::Pomodoro];
+
+    fn css_class(&self) -> &'static str {
+        match self {
+            TimerMode::Timer => "countdown",
+            TimerMode::Stopwatch => "stopwatch",
+            TimerMode::Pomodoro => "pomodoro",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TimerMode::Timer => "Timer",
+            TimerMode::Stopwatch => "Stopwatch",
+            TimerMode::Pomodoro => "Pomodoro",
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        match self {
+            TimerMode::Timer => "alarm-symbolic",
+            TimerMode::Stopwatch => "preferences-system-time-symbolic",
+            TimerMode::Pomodoro => "emoji-food-symbolic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Phase {
+    #[default]
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+/// Saved in `$XDG_STATE_HOME/rgb/timer.json`.
+/// Times are wall clock, so a running timer keeps going while the bar is closed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TimerState {
+    mode: TimerMode,
+    /// Countdown length in seconds, unused by stopwatch
+    duration: u64,
+    /// Milliseconds counted before the last start
+    elapsed: i64,
+    /// Unix time in milliseconds of the last start, `None` while paused
+    started_at: Option<i64>,
+    phase: Phase,
+    /// Work periods finished since the last long break
+    pomodoros: u32,
+}
+
+impl TimerState {
+    fn path() -> PathBuf {
+        glib::user_state_dir().join("rgb").join("timer.json")
+    }
+
+    fn load() -> Option<Self> {
+        let path = Self::path();
+        let text = std::fs::read_to_string(&path).ok()?;
+        serde_json::from_str(&text)
+            .inspect_err(|err| error!("Error parsing timer state {}: {err}", path.display()))
+            .ok()
+    }
+
+    fn save(&self) {
+        let path = Self::path();
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| serde_json::to_string(self).map_err(std::io::Error::from))
+            .and_then(|text| std::fs::write(&path, text));
+        if let Err(err) = result {
+            error!("Error saving timer state {}: {err}", path.display());
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    fn elapsed(&self, now: i64) -> i64 {
+        self.elapsed + self.started_at.map_or(0, |it| now - it)
+    }
+
+    fn remaining(&self, now: i64) -> i64 {
+        self.duration as i64 * 1000 - self.elapsed(now)
+    }
+
+    fn start(&mut self, now: i64) {
+        self.started_at.get_or_insert(now);
+    }
+
+    fn pause(&mut self, now: i64) {
+        self.elapsed = self.elapsed(now);
+        self.started_at = None;
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0;
+        self.started_at = None;
+    }
+
+    /// Countdown reached zero and wasn't handled yet, possibly while the bar was closed
+    fn is_finished(&self, now: i64) -> bool {
+        self.is_running() && self.mode != TimerMode::Stopwatch && self.remaining(now) <= 0
+    }
+
+    /// Phase after the current pomodoro one, counting finished work periods
+    fn next_phase(&mut self, long_break_every: u32) -> Phase {
+        match self.phase {
+            Phase::Work => {
+                self.pomodoros += 1;
+                if self.pomodoros % long_break_every == 0 {
+                    Phase::LongBreak
+                } else {
+                    Phase::ShortBreak
+                }
+            }
+            Phase::ShortBreak | Phase::LongBreak => Phase::Work,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TimerMessage {
+    Tick,
+    /// Starts or pauses
+    Toggle,
+    Reset,
+    /// Changes countdown length by this many scroll steps
+    Adjust(i64),
+    /// Starts a countdown of this many minutes
+    Preset(u32),
+    Mode(TimerMode),
+}
+
+#[relm4::component(pub async)]
+impl SimpleAsyncComponent for Timer {
+    type Input = TimerMessage;
+    type Output = ();
+    type Init = TimerInit;
+
+    view! {
+        #[root]
+        gtk::Box {
+            #[watch]
+            set_css_classes: &model.css_classes(),
+            #[watch]
+            set_tooltip_text: Some(model.tooltip().as_str()),
+            set_spacing: 4,
+
+            set_parent_for: popover = &gtk::PopoverMenu::from_model(Some(&model.menu)),
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gdk::BUTTON_PRIMARY,
+                connect_pressed[sender] => move |_, _, _, _| {
+                    sender.input(TimerMessage::Toggle);
+                }
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gdk::BUTTON_SECONDARY,
+                connect_pressed[popover] => move |_, _, _, _| {
+                    popover.popup();
+                }
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gdk::BUTTON_MIDDLE,
+                connect_pressed[sender] => move |_, _, _, _| {
+                    sender.input(TimerMessage::Reset);
+                }
+            },
+
+            add_controller = gtk::EventControllerScroll::new(EventControllerScrollFlags::VERTICAL) {
+                connect_scroll[sender] => move |_, _, y| {
+                    if y < 0f64 {
+                        sender.input(TimerMessage::Adjust(1));
+                    } else if y > 0f64 {
+                        sender.input(TimerMessage::Adjust(-1));
+                    }
+                    Propagation::Stop
+                }
+            },
+
+            gtk::Image {
+                #[watch]
+                set_icon_name: Some(model.state.mode.icon()),
+                add_css_class: "timer__icon",
+            },
+
+            gtk::Label {
+                #[watch]
+                set_label: &model.label,
+                add_css_class: "timer__label",
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        // Zero minutes would finish right away
+        let presets: Vec<u32> = init.presets.into_iter().filter(|it| *it > 0).collect();
+        let state = TimerState::load().unwrap_or_else(|| TimerState {
+            duration: presets.first().map_or(5 * 60, |it| u64::from(*it) * 60),
+            ..TimerState::default()
+        });
+        let mut model = Self {
+            state,
+            presets,
+            step: init.step.max(1),
+            work: init.work.saturating_mul(60).max(MIN_PERIOD),
+            short_break: init.short_break.saturating_mul(60).max(MIN_PERIOD),
+            long_break: init.long_break.saturating_mul(60).max(MIN_PERIOD),
+            long_break_every: init.long_break_every.max(1),
+            menu: gio::Menu::new(),
+            label: String::new(),
+            progress: None,
+            task: None,
+        };
+        model.build_menu();
+        let now = Utc::now().timestamp_millis();
+        if model.finish_period(now) {
+            model.state.save();
+        }
+        model.refresh(now);
+        model.schedule_ticks(sender.clone());
+        let widgets = view_output!();
+
+        let preset: RelmAction<PresetAction> = {
+            let sender = sender.clone();
+            RelmAction::new_with_target_value(move |_action, minutes: u32| {
+                sender.input(TimerMessage::Preset(minutes));
+            })
+        };
+        let mode: RelmAction<ModeAction> = {
+            let sender = sender.clone();
+            RelmAction::new_with_target_value(move |_action, index: u8| {
+                if let Some(mode) = TimerMode::ALL.get(usize::from(index)) {
+                    sender.input(TimerMessage::Mode(*mode));
+                }
+            })
+        };
+        let reset: RelmAction<ResetAction> = RelmAction::new_stateless(move |_action| {
+            sender.input(TimerMessage::Reset);
+        });
+
+        let mut group = RelmActionGroup::<TimerActionGroup>::new();
+        group.add_action(preset);
+        group.add_action(mode);
+        group.add_action(reset);
+        group.register_for_widget(&widgets.popover);
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
+        let now = Utc::now().timestamp_millis();
+        let changed = match message {
+            TimerMessage::Tick => self.finish_period(now),
+            TimerMessage::Toggle => {
+                if self.state.is_running() {
+                    self.state.pause(now);
+                } else {
+                    if self.state.mode != TimerMode::Stopwatch && self.state.remaining(now) <= 0 {
+                        self.state.reset();
+                    }
+                    self.state.start(now);
+                }
+                true
+            }
+            TimerMessage::Reset => {
+                self.state.reset();
+                if self.state.mode == TimerMode::Pomodoro {
+                    self.set_phase(Phase::Work);
+                    self.state.pomodoros = 0;
+                }
+                true
+            }
+            TimerMessage::Adjust(steps) => {
+                if self.state.mode != TimerMode::Timer {
+                    return;
+                }
+                let duration = self.state.duration as i64 + steps * self.step as i64;
+                self.state.duration = duration.clamp(MIN_PERIOD as i64, MAX_DURATION as i64) as u64;
+                true
+            }
+            TimerMessage::Preset(minutes) => {
+                self.state.mode = TimerMode::Timer;
+                self.state.duration = u64::from(minutes) * 60;
+                self.state.reset();
+                self.state.start(now);
+                true
+            }
+            TimerMessage::Mode(mode) => {
+                if self.state.mode == mode {
+                    return;
+                }
+                self.state.mode = mode;
+                self.state.reset();
+                match mode {
+                    TimerMode::Timer => {
+                        let minutes = self.presets.first().copied().unwrap_or(5);
+                        self.state.duration = u64::from(minutes) * 60;
+                    }
+                    TimerMode::Stopwatch => self.state.duration = 0,
+                    TimerMode::Pomodoro => {
+                        self.state.pomodoros = 0;
+                        self.set_phase(Phase::Work);
+                    }
+                }
+                true
+            }
+        };
+        if changed {
+            self.state.save();
+            self.schedule_ticks(sender);
+        }
+        self.refresh(now);
+    }
+}
+
+impl Timer {
+    fn build_menu(&self) {
+        let presets = gio::Menu::new();
+        for minutes in &self.presets {
+            let item = gio::MenuItem::new(Some(&format_duration(i64::from(*minutes) * 60)), None);
+            item.set_action_and_target_value(Some("timer.preset"), Some(&minutes.to_variant()));
+            presets.append_item(&item);
+        }
+        let modes = gio::Menu::new();
+        for (index, mode) in TimerMode::ALL.iter().enumerate() {
+            let item = gio::MenuItem::new(Some(mode.name()), None);
+            item.set_action_and_target_value(Some("timer.mode"), Some(&(index as u8).to_variant()));
+            modes.append_item(&item);
+        }
+        self.menu.append_section(None, &presets);
+        self.menu.append_section(None, &modes);
+        self.menu.append(Some("Reset"), Some("timer.reset"));
+    }
+
+    fn set_phase(&mut self, phase: Phase) {
+        self.state.phase = phase;
+        self.state.duration = match phase {
+            Phase::Work => self.work,
+            Phase::ShortBreak => self.short_break,
+            Phase::LongBreak => self.long_break,
+        };
+    }
+
+    /// Handles a countdown reaching zero, returns whether state changed
+    fn finish_period(&mut self, now: i64) -> bool {
+        let state = &mut self.state;
+        if !state.is_finished(now) {
+            return false;
+        }
+        match state.mode {
+            TimerMode::Pomodoro => {
+                let phase = state.next_phase(self.long_break_every);
+                let title = match phase {
+                    Phase::Work => "Back to work",
+                    Phase::ShortBreak => "Time for a break",
+                    Phase::LongBreak => "Time for a long break",
+                };
+                self.set_phase(phase);
+                self.state.reset();
+                self.state.start(now);
+                send_notification(title, &format_duration(self.state.duration as i64));
+            }
+            _ => {
+                state.reset();
+                send_notification("Time is up", &format_duration(state.duration as i64));
+            }
+        }
+        true
+    }
+
+    /// Ticks once a second while running, aligned to the shown seconds
+    fn schedule_ticks(&mut self, sender: AsyncComponentSender<Self>) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        if !self.state.is_running() {
+            return;
+        }
+        let state = self.state.clone();
+        self.task = Some(relm4::spawn(async move {
+            loop {
+                let elapsed = state.elapsed(Utc::now().timestamp_millis());
+                let delay = 1000 - elapsed.rem_euclid(1000);
+                tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+                sender.input(TimerMessage::Tick);
+            }
+        }));
+    }
+
+    fn refresh(&mut self, now: i64) {
+        self.label = match self.state.mode {
+            TimerMode::Stopwatch => format_duration(self.state.elapsed(now) / 1000),
+            // Rounded up, so zero is shown only when the time is up
+            _ => format_duration((self.state.remaining(now).max(0) + 999) / 1000),
+        };
+        let total = self.state.duration as i64 * 1000;
+        self.progress = (self.state.mode != TimerMode::Stopwatch && total > 0).then(|| {
+            let elapsed = self.state.elapsed(now).clamp(0, total);
+            format!("progress-{}", elapsed * 10 / total * 10)
+        });
+    }
+
+    fn css_classes(&self) -> Vec<&str> {
+        let mut classes = vec!["module", "hoverable", "timer", self.state.mode.css_class()];
+        if self.state.is_running() {
+            classes.push("running");
+        } else if self.state.elapsed != 0 {
+            classes.push("paused");
+        }
+        if self.state.mode == TimerMode::Pomodoro {
+            classes.push(match self.state.phase {
+                Phase::Work => "work",
+                Phase::ShortBreak | Phase::LongBreak => "break",
+            });
+        }
+        if let Some(progress) = &self.progress {
+            classes.push(progress);
+        }
+        classes
+    }
+
+    fn tooltip(&self) -> String {
+        let mode = self.state.mode.name();
+        match self.state.mode {
+            TimerMode::Pomodoro => {
+                let phase = match self.state.phase {
+                    Phase::Work => "work",
+                    Phase::ShortBreak => "short break",
+                    Phase::LongBreak => "long break",
+                };
+                let done = self.state.pomodoros % self.long_break_every;
+                format!("{mode}: {phase}, {done}/{}", self.long_break_every)
+            }
+            _ => mode.to_string(),
+        }
+    }
+}
+
+/// `M:SS` or `H:MM:SS`
+fn format_duration(seconds: i64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+fn send_notification(title: &str, body: &str) {
+    info!("Timer: {title}");
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(body));
+    relm4::main_application().send_notification(Some("timer"), &notification);
+    debug!("Sent timer notification");
+}
+
+relm4::new_action_group!(TimerActionGroup, "timer");
+new_stateless_value_action!(PresetAction, TimerActionGroup, "preset", u32);
+new_stateless_value_action!(ModeAction, TimerActionGroup, "mode", u8);
+relm4::new_stateless_action!(ResetAction, TimerActionGroup, "reset");
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, Phase, TimerMode, TimerState};
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0:00");
+        assert_eq!(format_duration(65), "1:05");
+        assert_eq!(format_duration(25 * 60), "25:00");
+        assert_eq!(format_duration(3600 + 61), "1:01:01");
+    }
+
+    #[test]
+    fn test_next_phase() {
+        let mut state = TimerState {
+            mode: TimerMode::Pomodoro,
+            ..TimerState::default()
+        };
+        let phases = (0..9)
+            .map(|_| {
+                state.phase = state.next_phase(4);
+                state.phase
+            })
+            .collect::<Vec<Phase>>();
+        assert_eq!(
+            phases,
+            [
+                Phase::ShortBreak,
+                Phase::Work,
+                Phase::ShortBreak,
+                Phase::Work,
+                Phase::ShortBreak,
+                Phase::Work,
+                Phase::LongBreak,
+                Phase::Work,
+                Phase::ShortBreak,
+            ]
+        );
+        assert_eq!(state.pomodoros, 5);
+    }
+
+    #[test]
+    fn test_restore_state() {
+        let state = TimerState {
+            mode: TimerMode::Pomodoro,
+            duration: 25 * 60,
+            elapsed: 60_000,
+            started_at: Some(1_000_000),
+            phase: Phase::Work,
+            pomodoros: 3,
+        };
+        let text = serde_json::to_string(&state).unwrap();
+        let restored: TimerState = serde_json::from_str(&text).unwrap();
+        // Kept running while the bar was closed
+        assert_eq!(restored.remaining(1_000_000 + 60_000), 23 * 60 * 1000);
+        assert!(!restored.is_finished(1_000_000 + 60_000));
+        assert!(restored.is_finished(1_000_000 + 24 * 60 * 1000));
+        assert_eq!(restored.pomodoros, 3);
+
+        // Missing fields are defaulted, a stopwatch never finishes
+        let restored: TimerState =
+            serde_json::from_str(r#"{"mode":"stopwatch","started_at":0}"#).unwrap();
+        assert_eq!(restored.mode, TimerMode::Stopwatch);
+        assert!(!restored.is_finished(60_000));
+    }
+}
@@ -1,33 +1,74 @@
+use crate::dbus::{DBusMenuProxy, RemovedProps, StatusNotifierItemProxy, UpdatedProps};
 use crate::ext::ParentFor;
-use gtk::gdk_pixbuf::Colorspace;
+use crate::widgets::tray_tooltip::{self, RichTooltip};
+use futures::StreamExt;
+use gtk::gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use gtk::glib::Propagation;
 use gtk::prelude::ButtonExt;
 use gtk::prelude::PopoverExt;
-use gtk::prelude::WidgetExt;
+use gtk::prelude::ToVariant;
 use gtk::prelude::{ActionMapExt, GestureSingleExt};
-use gtk::{gdk, gio, glib};
+use gtk::prelude::{Cast, WidgetExt};
+use gtk::{gdk, gio, glib, EventControllerScrollFlags};
 use log::{debug, error};
 use relm4::factory::FactoryComponent;
 use relm4::FactorySender;
+use relm4::RelmWidgetExt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
 use std::rc::Rc;
+use std::time::Duration;
 use system_tray::client::{ActivateRequest, Client, UpdateEvent};
-use system_tray::item::StatusNotifierItem;
+use system_tray::item::{IconPixmap, Status, StatusNotifierItem};
 use system_tray::menu::{MenuItem, MenuType, ToggleState, ToggleType, TrayMenu};
+use tokio::task::JoinHandle;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct TrayItem {
     pub key: String,
     item: StatusNotifierItem,
     menu: Option<TrayMenu>,
     client: Rc<Client>,
+    /// Hides the item while its status is passive
+    hide_passive: bool,
+    /// Listens for menu property changes
+    menu_task: Option<JoinHandle<()>>,
+    tooltip: RichTooltip,
+    /// Looks up icons in the item's own theme path
+    icon_theme: Option<gtk::IconTheme>,
+    /// Name and pixel size last looked up in the item's theme for each image,
+    /// lookups make a new paintable every time
+    theme_icons: RefCell<HashMap<gtk::Image, (String, i32)>>,
 }
 
-impl From<(String, (StatusNotifierItem, Option<TrayMenu>), Rc<Client>)> for TrayItem {
-    fn from(value: (String, (StatusNotifierItem, Option<TrayMenu>), Rc<Client>)) -> Self {
+impl
+    From<(
+        String,
+        (StatusNotifierItem, Option<TrayMenu>),
+        Rc<Client>,
+        bool,
+    )> for TrayItem
+{
+    fn from(
+        value: (
+            String,
+            (StatusNotifierItem, Option<TrayMenu>),
+            Rc<Client>,
+            bool,
+        ),
+    ) -> Self {
         Self {
             key: value.0,
             item: value.1 .0,
             menu: value.1 .1,
             client: value.2,
+            hide_passive: value.3,
+            menu_task: None,
+            tooltip: RichTooltip::new(),
+            icon_theme: None,
+            theme_icons: RefCell::new(HashMap::new()),
         }
     }
 }
@@ -37,6 +78,7 @@ impl
         &String,
         &(StatusNotifierItem, Option<TrayMenu>),
         &Rc<Client>,
+        bool,
     )> for TrayItem
 {
     fn from(
@@ -44,6 +86,7 @@ impl
             &String,
             &(StatusNotifierItem, Option<TrayMenu>),
             &Rc<Client>,
+            bool,
         ),
     ) -> Self {
         Self {
@@ -51,15 +94,51 @@ impl
             item: value.1 .0.clone(),
             menu: value.1 .1.clone(),
             client: value.2.clone(),
+            hide_passive: value.3,
+            menu_task: None,
+            tooltip: RichTooltip::new(),
+            icon_theme: None,
+            theme_icons: RefCell::new(HashMap::new()),
         }
     }
 }
 
+/// Size of the icon in logical pixels
+const ICON_SIZE: i32 = 16;
+const OVERLAY_SIZE: i32 = 8;
+
+/// How long the item may prepare its menu before it's shown anyway
+const ABOUT_TO_SHOW_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub enum TrayItemMessage {
+    Update(UpdateEvent),
+    /// Left click
+    Activate,
+    /// Middle click
+    SecondaryActivate,
+    Scroll {
+        delta: i32,
+        orientation: &'static str,
+    },
+    /// Lets the item update the menu before it's shown
+    ShowMenu,
+    PopupMenu,
+    MenuProperties(UpdatedProps, RemovedProps),
+    /// Monitor scale changed, pixmaps are picked again
+    ScaleChanged,
+}
+
 #[relm4::factory(pub)]
 impl FactoryComponent for TrayItem {
-    type Input = system_tray::client::UpdateEvent;
+    type Input = TrayItemMessage;
     type Output = ();
-    type Init = (String, (StatusNotifierItem, Option<TrayMenu>), Rc<Client>);
+    type Init = (
+        String,
+        (StatusNotifierItem, Option<TrayMenu>),
+        Rc<Client>,
+        bool,
+    );
     type ParentWidget = gtk::Box;
     type CommandOutput = ();
 
@@ -69,59 +148,86 @@ impl FactoryComponent for TrayItem {
         gtk::Button {
             add_css_class: "systray__icon",
             #[watch]
+            set_class_active: ("passive", matches!(self.item.status, Status::Passive)),
+            #[watch]
+            set_class_active: ("active", matches!(self.item.status, Status::Active)),
+            #[watch]
+            set_class_active: ("needs-attention", self.needs_attention()),
+            #[watch]
+            set_visible: !(self.hide_passive && matches!(self.item.status, Status::Passive)),
+            #[watch]
             set_tooltip_text: self.item.title.as_deref(),
+            // Title is the fallback when the item has no tooltip
             #[watch]
-            set_icon_name: self.item.icon_name.as_deref().unwrap_or("icon"),
+            set_has_tooltip: true,
+            connect_query_tooltip[rich_tooltip = self.tooltip.root.clone()] => move |_, _, _, _, tooltip| {
+                if !rich_tooltip.is_visible() {
+                    return false;
+                }
+                tooltip.set_custom(Some(&rich_tooltip));
+                true
+            },
             set_parent_for: popover = &gtk::PopoverMenu::from_model(self.build_model().as_ref()),
-            connect_clicked: glib::clone!(
-                #[strong(rename_to = key)]
-                self.key,
-                #[strong(rename_to = menu)]
-                self.item.menu,
-                #[strong(rename_to = client)]
-                self.client,
-                move |_btn| {
-                    let key = key.clone();
-                    let menu = menu.clone();
-                    let client = client.clone();
-                    relm4::spawn_local(async move {
-                        let key_dbg = key.clone();
-                        let rq = ActivateRequest {
-                            address: key,
-                            menu_path: menu.unwrap(),
-                            submenu_id: 0,
-                        };
-                        match client.activate(rq).await {
-                            Ok(()) => {
-                                debug!("Sent activate request to {key_dbg}!")
-                            }
-                            Err(err) => {
-                                error!("{err}")
-                            }
-                        };
-                    });
-                }),
+            connect_clicked => TrayItemMessage::Activate,
+
+            gtk::Overlay {
+                #[name = "icon"]
+                gtk::Image {
+                    set_pixel_size: ICON_SIZE,
+                    connect_scale_factor_notify => TrayItemMessage::ScaleChanged,
+                },
+
+                add_overlay: overlay_icon = &gtk::Image {
+                    add_css_class: "systray__overlay",
+                    set_halign: gtk::Align::End,
+                    set_valign: gtk::Align::End,
+                    set_pixel_size: OVERLAY_SIZE,
+                    set_can_target: false,
+                },
+            },
+
+            add_controller = gtk::GestureClick::new() {
+                set_button: gdk::BUTTON_MIDDLE,
+                connect_pressed[sender] => move |_, _, _, _| {
+                    sender.input(TrayItemMessage::SecondaryActivate);
+                }
+            },
 
             add_controller = gtk::GestureClick::new() {
                 set_button: gdk::BUTTON_SECONDARY,
-                connect_pressed[popover] => move |_, _, _, _| {
-                    popover.popup();
+                connect_pressed[sender] => move |_, _, _, _| {
+                    sender.input(TrayItemMessage::ShowMenu);
+                }
+            },
+
+            add_controller = gtk::EventControllerScroll::new(
+                EventControllerScrollFlags::BOTH_AXES | EventControllerScrollFlags::DISCRETE
+            ) {
+                connect_scroll[sender] => move |_, dx, dy| {
+                    if dy != 0f64 {
+                        sender.input(TrayItemMessage::Scroll { delta: dy as i32, orientation: "vertical" });
+                    }
+                    if dx != 0f64 {
+                        sender.input(TrayItemMessage::Scroll { delta: dx as i32, orientation: "horizontal" });
+                    }
+                    Propagation::Stop
                 }
             },
         }
     }
 
     fn init_model(init: Self::Init, _index: &Self::Index, sender: FactorySender<Self>) -> Self {
-        let model = Self::from(init);
-        {
-            let icon = model.item.icon_name.clone();
-            let icon_theme_path = model.item.icon_theme_path.clone();
-            if let Some(icon_theme_path) = icon_theme_path {
-                gtk::IconTheme::for_display(&gdk::Display::default().unwrap())
-                    .add_search_path(icon_theme_path);
-            }
-            sender.input(UpdateEvent::Icon(icon));
-        }
+        let mut model = Self::from(init);
+        model.menu_task = model.watch_menu(sender.clone());
+        model.icon_theme = model
+            .item
+            .icon_theme_path
+            .as_deref()
+            .filter(|it| !it.is_empty())
+            .map(icon_theme_for);
+        model.update_tooltip();
+        let icon = model.item.icon_name.clone();
+        sender.input(TrayItemMessage::Update(UpdateEvent::Icon(icon)));
         model
     }
 
@@ -129,52 +235,290 @@ impl FactoryComponent for TrayItem {
         &mut self,
         widgets: &mut Self::Widgets,
         message: Self::Input,
-        _sender: FactorySender<Self>,
+        sender: FactorySender<Self>,
     ) {
+        let message = match message {
+            TrayItemMessage::Update(message) => message,
+            TrayItemMessage::Activate if self.item.item_is_menu => {
+                self.about_to_show(sender);
+                return;
+            }
+            TrayItemMessage::Activate => {
+                self.call_item(
+                    sender,
+                    true,
+                    |item| async move { item.activate(0, 0).await },
+                );
+                return;
+            }
+            TrayItemMessage::SecondaryActivate => {
+                self.call_item(sender, false, |item| async move {
+                    item.secondary_activate(0, 0).await
+                });
+                return;
+            }
+            TrayItemMessage::Scroll { delta, orientation } => {
+                self.call_item(sender, false, move |item| async move {
+                    item.scroll(delta, orientation).await
+                });
+                return;
+            }
+            TrayItemMessage::ShowMenu => {
+                self.about_to_show(sender);
+                return;
+            }
+            TrayItemMessage::PopupMenu => {
+                self.show_menu(widgets);
+                return;
+            }
+            TrayItemMessage::MenuProperties(updated, removed) => {
+                if let Some(menu) = &mut self.menu {
+                    apply_properties(&mut menu.submenus, updated, removed);
+                    self.refresh_menu(widgets);
+                }
+                return;
+            }
+            TrayItemMessage::ScaleChanged => {
+                self.update_icons(widgets);
+                return;
+            }
+        };
         match message {
             UpdateEvent::Icon(icon) => {
-                self.item.icon_name = icon.clone();
-                if let Some(icon) = icon {
-                    widgets.root.set_icon_name(icon.as_str());
-                }
-                if let Some(icon) = self.item.icon_pixmap.clone() {
-                    if let Some(icon) = icon.iter().max_by(|a, b| a.height.cmp(&b.height)) {
-                        let pixels = icon.pixels.clone();
-                        let vec = argb2rgba(pixels);
-                        let bytes = glib::Bytes::from_owned(vec);
-                        let pixbuf = gtk::gdk_pixbuf::Pixbuf::from_bytes(
-                            &bytes,
-                            Colorspace::Rgb,
-                            true,
-                            8,
-                            icon.width,
-                            icon.height,
-                            icon.width * 4,
-                        );
-                        let icon = gtk::Image::from_pixbuf(Some(&pixbuf));
-                        widgets.root.set_child(Some(&icon))
-                    }
-                }
+                self.item.icon_name = icon;
+                self.update_icons(widgets);
             }
+            UpdateEvent::AttentionIcon(icon) => {
+                self.item.attention_icon_name = icon;
+                self.update_icons(widgets);
+            }
+            UpdateEvent::OverlayIcon(icon) => {
+                self.item.overlay_icon_name = icon;
+                self.update_icons(widgets);
+            }
+            UpdateEvent::Status(status) => {
+                self.item.status = status;
+                self.update_icons(widgets);
+            }
+            // Sent by the tray client on `LayoutUpdated`
             UpdateEvent::Menu(menu) => {
                 self.menu = Some(menu);
-                if let Some(action) = self.build_action_group() {
-                    widgets.popover.insert_action_group("tray", Some(&action));
-                }
-                if let Some(menu) = self.build_model() {
-                    widgets.popover.set_menu_model(Some(&menu));
-                }
+                self.refresh_menu(widgets);
             }
             UpdateEvent::Title(title) => {
                 self.item.title = title;
                 widgets.root.set_tooltip_text(self.item.title.as_deref())
             }
+            UpdateEvent::Tooltip(tooltip) => {
+                self.item.tool_tip = tooltip;
+                self.update_tooltip();
+            }
             _ => {}
         };
+        self.update_view(widgets, sender);
     }
 }
 
 impl TrayItem {
+    fn needs_attention(&self) -> bool {
+        matches!(self.item.status, Status::NeedsAttention)
+    }
+
+    /// Attention icon replaces the normal one while attention is requested,
+    /// names take priority over pixmaps
+    fn update_icons(&self, widgets: &TrayItemWidgets) {
+        let item = &self.item;
+        let attention = (&item.attention_icon_name, &item.attention_icon_pixmap);
+        let normal = (&item.icon_name, &item.icon_pixmap);
+        let (name, pixmap) = if self.needs_attention() && has_icon(attention) {
+            attention
+        } else {
+            normal
+        };
+        self.set_icon(&widgets.icon, name.as_deref(), pixmap.as_deref(), ICON_SIZE);
+        if !has_icon((name, pixmap)) {
+            widgets.icon.set_icon_name(Some("icon"));
+        }
+
+        let (name, pixmap) = (&item.overlay_icon_name, &item.overlay_icon_pixmap);
+        widgets.overlay_icon.set_visible(has_icon((name, pixmap)));
+        self.set_icon(
+            &widgets.overlay_icon,
+            name.as_deref(),
+            pixmap.as_deref(),
+            OVERLAY_SIZE,
+        );
+    }
+
+    fn update_tooltip(&self) {
+        let tooltip = self.item.tool_tip.as_ref();
+        let visible = self.tooltip.update(tooltip);
+        self.tooltip.root.set_visible(visible);
+        if let Some(tooltip) = tooltip {
+            let has_icon = !tooltip.icon_name.is_empty() || !tooltip.icon_data.is_empty();
+            self.tooltip.icon.set_visible(has_icon);
+            self.set_icon(
+                &self.tooltip.icon,
+                Some(tooltip.icon_name.as_str()),
+                Some(tooltip.icon_data.as_slice()),
+                tray_tooltip::ICON_SIZE,
+            );
+        }
+    }
+
+    /// Only changed icons are set again, so updates don't rebuild the image.
+    /// Names are looked up in the item's theme path first, then in the system theme,
+    /// the pixmap is used when neither has the name.
+    fn set_icon(
+        &self,
+        image: &gtk::Image,
+        name: Option<&str>,
+        pixmap: Option<&[IconPixmap]>,
+        size: i32,
+    ) {
+        let name = name.filter(|it| !it.is_empty());
+        let pixel_size = size * image.scale_factor();
+        let texture = pixmap.and_then(|it| texture_for(it, pixel_size));
+        let theme = self.icon_theme.as_ref();
+        let mut theme_icons = self.theme_icons.borrow_mut();
+        match (name, texture) {
+            (Some(name), texture) => match theme.filter(|it| it.has_icon(name)) {
+                Some(theme) => {
+                    let key = (name.to_string(), pixel_size);
+                    if theme_icons.get(image) == Some(&key) {
+                        return;
+                    }
+                    theme_icons.insert(image.clone(), key);
+                    let paintable = theme
+                        .lookup_icon(
+                            name,
+                            &[],
+                            size,
+                            image.scale_factor(),
+                            gtk::TextDirection::None,
+                            gtk::IconLookupFlags::empty(),
+                        )
+                        .upcast::<gdk::Paintable>();
+                    set_paintable(image, paintable);
+                }
+                None => {
+                    theme_icons.remove(image);
+                    let system = gtk::IconTheme::for_display(&image.display());
+                    match texture.filter(|_| !system.has_icon(name)) {
+                        Some(texture) => set_paintable(image, texture.upcast()),
+                        None => {
+                            if image.icon_name().as_deref() != Some(name) {
+                                image.set_icon_name(Some(name));
+                            }
+                        }
+                    }
+                }
+            },
+            (None, Some(texture)) => {
+                theme_icons.remove(image);
+                set_paintable(image, texture.upcast());
+            }
+            (None, None) => {
+                theme_icons.remove(image);
+                image.clear();
+            }
+        }
+    }
+
+    fn refresh_menu(&self, widgets: &TrayItemWidgets) {
+        if let Some(action) = self.build_action_group() {
+            widgets.popover.insert_action_group("tray", Some(&action));
+        }
+        if let Some(menu) = self.build_model() {
+            widgets.popover.set_menu_model(Some(&menu));
+        }
+    }
+
+    /// Property changes aren't part of the layout, so they come from the menu directly
+    fn watch_menu(&self, sender: FactorySender<Self>) -> Option<JoinHandle<()>> {
+        let key = self.key.clone();
+        let menu_path = self.item.menu.clone()?;
+        Some(relm4::spawn(async move {
+            let result: zbus::Result<()> = async {
+                let connection = crate::dbus::session().await?;
+                let menu = DBusMenuProxy::for_item(&connection, &key, &menu_path).await?;
+                let mut updates = menu.receive_items_properties_updated().await?;
+                while let Some(signal) = updates.next().await {
+                    let args = signal.args()?;
+                    sender.input(TrayItemMessage::MenuProperties(
+                        args.updated_props,
+                        args.removed_props,
+                    ));
+                }
+                Ok(())
+            }
+            .await;
+            if let Err(err) = result {
+                error!("Error watching menu of tray item {key}: {err}");
+            }
+        }))
+    }
+
+    /// The menu is shown even if the item doesn't answer
+    fn about_to_show(&self, sender: FactorySender<Self>) {
+        let key = self.key.clone();
+        let Some(menu_path) = self.item.menu.clone() else {
+            sender.input(TrayItemMessage::PopupMenu);
+            return;
+        };
+        relm4::spawn(async move {
+            let result = async {
+                let connection = crate::dbus::session().await?;
+                let menu = DBusMenuProxy::for_item(&connection, &key, &menu_path).await?;
+                menu.about_to_show(0).await
+            };
+            match tokio::time::timeout(ABOUT_TO_SHOW_TIMEOUT, result).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => debug!("Tray item {key} ignored AboutToShow: {err}"),
+                Err(_) => debug!("Tray item {key} didn't answer AboutToShow in time"),
+            }
+            sender.input(TrayItemMessage::PopupMenu);
+        });
+    }
+
+    /// Items without a menu have nothing to show
+    fn show_menu(&self, widgets: &TrayItemWidgets) {
+        if self.item.menu.is_some() && self.menu.is_some() {
+            widgets.popover.popup();
+        } else {
+            debug!("Tray item {} has no menu", self.key);
+        }
+    }
+
+    /// Calls a method of the item, with `menu_on_error` items that
+    /// don't implement it get their menu shown instead
+    fn call_item<F, Fut>(&self, sender: FactorySender<Self>, menu_on_error: bool, call: F)
+    where
+        F: FnOnce(StatusNotifierItemProxy<'static>) -> Fut + 'static,
+        Fut: std::future::Future<Output = zbus::Result<()>>,
+    {
+        let key = self.key.clone();
+        relm4::spawn_local(async move {
+            let item = match crate::dbus::session().await {
+                Ok(connection) => StatusNotifierItemProxy::for_address(&connection, &key).await,
+                Err(err) => Err(err),
+            };
+            let result = match item {
+                Ok(item) => call(item).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(()) => debug!("Sent request to tray item {key}"),
+                Err(err) => {
+                    error!("Error calling tray item {key}: {err}");
+                    if menu_on_error {
+                        sender.input(TrayItemMessage::ShowMenu);
+                    }
+                }
+            }
+        });
+    }
+
     fn build_model(&self) -> Option<gio::Menu> {
         let value = self.menu.clone()?.submenus;
         self.build_menu(value)
@@ -214,10 +558,7 @@ impl TrayItem {
             return Some(gio::MenuItem::new_submenu(value.label.as_deref(), &menu));
         }
 
-        let item = gio::MenuItem::new(
-            value.label.as_deref(),
-            Some(format!("{},{}", "tray", value.id).as_str()),
-        );
+        let item = gio::MenuItem::new(value.label.as_deref(), None);
 
         if let Some(icon_data) = value.icon_data {
             let bytes = glib::Bytes::from_owned(argb2rgba(icon_data));
@@ -234,8 +575,11 @@ impl TrayItem {
                 }
             }
         }
-        let detailed_action: String = "tray.".to_owned() + value.id.to_string().as_str();
-        item.set_detailed_action(detailed_action.as_str());
+        // Radio items are drawn as such only when they have a target,
+        // the check is shown while the action state equals it
+        let action = format!("tray.{}", value.id);
+        let target = matches!(value.toggle_type, ToggleType::Radio).then(|| true.to_variant());
+        item.set_action_and_target_value(Some(action.as_str()), target.as_ref());
 
         Some(item)
     }
@@ -264,21 +608,21 @@ impl TrayItem {
                 vec.append(actions.as_mut());
             }
         } else {
-            let variant_value = match value.toggle_type {
-                ToggleType::Checkmark | ToggleType::Radio => match value.toggle_state {
-                    ToggleState::On => glib::Variant::from(true),
-                    ToggleState::Off => glib::Variant::from(false),
-                    ToggleState::Indeterminate => glib::Variant::from(-1),
-                },
-                ToggleType::CannotBeToggled => glib::Variant::from_none(glib::VariantTy::INT32),
+            let name = value.id.to_string();
+            let state = matches!(value.toggle_state, ToggleState::On).to_variant();
+            // The state is only changed by the item, it sends updated properties after activation
+            let action = match value.toggle_type {
+                ToggleType::Checkmark => gio::SimpleAction::new_stateful(&name, None, &state),
+                ToggleType::Radio => {
+                    gio::SimpleAction::new_stateful(&name, Some(glib::VariantTy::BOOLEAN), &state)
+                }
+                ToggleType::CannotBeToggled => gio::SimpleAction::new(&name, None),
             };
-            let action = gio::SimpleAction::new_stateful(
-                value.id.to_string().as_str(),
-                None,
-                &variant_value,
-            );
             action.set_enabled(value.enabled);
-            let menu_path = Box::new(self.item.menu.clone().unwrap());
+            let Some(menu_path) = self.item.menu.clone() else {
+                return vec;
+            };
+            let menu_path = Box::new(menu_path);
             let key = Box::new(self.key.clone());
             let client = self.client.clone();
             action.connect_activate(move |_it, _state| {
@@ -306,9 +650,183 @@ impl TrayItem {
     }
 }
 
+impl Drop for TrayItem {
+    fn drop(&mut self) {
+        if let Some(task) = self.menu_task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Applies `ItemsPropertiesUpdated`, removed properties go back to their defaults
+fn apply_properties(items: &mut [MenuItem], updated: UpdatedProps, removed: RemovedProps) {
+    for (id, properties) in updated {
+        let Some(item) = find_item(items, id) else {
+            continue;
+        };
+        for (name, value) in properties {
+            match name.as_str() {
+                "label" => item.label = value.downcast_ref::<str>().map(str::to_string),
+                "enabled" => item.enabled = value.downcast_ref::<bool>().copied().unwrap_or(true),
+                "visible" => item.visible = value.downcast_ref::<bool>().copied().unwrap_or(true),
+                "icon-name" => item.icon_name = value.downcast_ref::<str>().map(str::to_string),
+                "toggle-state" => {
+                    item.toggle_state = match value.downcast_ref::<i32>() {
+                        Some(1) => ToggleState::On,
+                        Some(0) => ToggleState::Off,
+                        _ => ToggleState::Indeterminate,
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    for (id, properties) in removed {
+        let Some(item) = find_item(items, id) else {
+            continue;
+        };
+        for name in properties {
+            match name.as_str() {
+                "label" => item.label = None,
+                "enabled" => item.enabled = true,
+                "visible" => item.visible = true,
+                "icon-name" => item.icon_name = None,
+                "toggle-state" => item.toggle_state = ToggleState::Indeterminate,
+                _ => {}
+            }
+        }
+    }
+}
+
+fn find_item(items: &mut [MenuItem], id: i32) -> Option<&mut MenuItem> {
+    for item in items {
+        if item.id == id {
+            return Some(item);
+        }
+        if let Some(item) = find_item(&mut item.submenu, id) {
+            return Some(item);
+        }
+    }
+    None
+}
+
+fn has_icon((name, pixmap): (&Option<String>, &Option<Vec<IconPixmap>>)) -> bool {
+    name.as_ref().is_some_and(|it| !it.is_empty())
+        || pixmap.as_ref().is_some_and(|it| !it.is_empty())
+}
+
+fn set_paintable(image: &gtk::Image, paintable: gdk::Paintable) {
+    if image.paintable().as_ref() != Some(&paintable) {
+        image.set_paintable(Some(&paintable));
+    }
+}
+
+/// Theme with only the item's path, apps put either plain files or a hicolor tree there
+fn icon_theme_for(path: &str) -> gtk::IconTheme {
+    let theme = gtk::IconTheme::new();
+    theme.set_search_path(&[Path::new(path)]);
+    theme.set_theme_name(Some("hicolor"));
+    theme
+}
+
+/// Max number of converted pixmaps kept around
+const CACHE_SIZE: usize = 64;
+
+thread_local! {
+    /// Textures by pixmap content and the size they were scaled to
+    static TEXTURES: RefCell<HashMap<u64, gdk::Texture>> = RefCell::new(HashMap::new());
+}
+
+/// Variant closest to the size in device pixels, scaled to it if it doesn't match
+fn texture_for(pixmap: &[IconPixmap], size: i32) -> Option<gdk::Texture> {
+    let icon = pixmap
+        .iter()
+        .filter(|it| it.width > 0 && it.height > 0)
+        .filter(|it| pixels_len(it).is_some_and(|len| it.pixels.len() >= len))
+        // Bigger variants win ties, downscaling looks better than upscaling
+        .min_by_key(|it| ((it.height - size).abs(), -it.height))?;
+
+    let mut hasher = DefaultHasher::new();
+    (icon.width, icon.height, &icon.pixels, size).hash(&mut hasher);
+    let key = hasher.finish();
+    if let Some(texture) = TEXTURES.with_borrow(|it| it.get(&key).cloned()) {
+        return Some(texture);
+    }
+
+    let bytes = glib::Bytes::from_owned(argb2rgba(icon.pixels.clone()));
+    let texture = if icon.height == size {
+        gdk::MemoryTexture::new(
+            icon.width,
+            icon.height,
+            gdk::MemoryFormat::R8g8b8a8,
+            &bytes,
+            icon.width as usize * 4,
+        )
+        .upcast()
+    } else {
+        let pixbuf = Pixbuf::from_bytes(
+            &bytes,
+            Colorspace::Rgb,
+            true,
+            8,
+            icon.width,
+            icon.height,
+            icon.width * 4,
+        );
+        // Sizes come from the item, so they are kept from overflowing
+        let width = i64::from(icon.width) * i64::from(size) / i64::from(icon.height);
+        let width = width.clamp(1, i64::from(i32::MAX)) as i32;
+        let pixbuf = pixbuf.scale_simple(width, size, InterpType::Hyper)?;
+        gdk::Texture::for_pixbuf(&pixbuf)
+    };
+
+    TEXTURES.with_borrow_mut(|it| {
+        if it.len() >= CACHE_SIZE {
+            it.clear();
+        }
+        it.insert(key, texture.clone());
+    });
+    Some(texture)
+}
+
+/// Bytes a pixmap of its size needs, `None` if that doesn't fit
+fn pixels_len(icon: &IconPixmap) -> Option<usize> {
+    let width = usize::try_from(icon.width).ok()?;
+    let height = usize::try_from(icon.height).ok()?;
+    width.checked_mul(height)?.checked_mul(4)
+}
+
+/// Pixmaps are ARGB32 in network byte order
 fn argb2rgba(mut argb: Vec<u8>) -> Vec<u8> {
     for x in argb.chunks_exact_mut(4) {
-        x.rotate_right(1);
+        x.rotate_left(1);
     }
     argb
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{argb2rgba, pixels_len};
+    use system_tray::item::IconPixmap;
+
+    #[test]
+    fn test_argb2rgba() {
+        // Opaque orange
+        assert_eq!(
+            argb2rgba(vec![0xff, 0xff, 0x80, 0x00]),
+            [0xff, 0x80, 0x00, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_pixels_len() {
+        let pixmap = |width, height| IconPixmap {
+            width,
+            height,
+            pixels: Vec::new(),
+        };
+        assert_eq!(pixels_len(&pixmap(16, 16)), Some(1024));
+        assert_eq!(pixels_len(&pixmap(-16, 16)), None);
+        assert!(pixels_len(&pixmap(i32::MAX, i32::MAX)).is_some_and(|it| it > 0));
+    }
+}
@@ -0,0 +1,193 @@
+use gtk::glib;
+use gtk::prelude::{BoxExt, WidgetExt};
+use system_tray::item::Tooltip;
+
+pub const ICON_SIZE: i32 = 32;
+
+/// Icon, title and description an item publishes in its `ToolTip` property
+#[derive(Debug, Clone)]
+pub struct RichTooltip {
+    pub root: gtk::Box,
+    pub icon: gtk::Image,
+    title: gtk::Label,
+    description: gtk::Label,
+}
+
+impl RichTooltip {
+    pub fn new() -> Self {
+        let root = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        root.add_css_class("systray__tooltip");
+        let icon = gtk::Image::new();
+        icon.set_valign(gtk::Align::Start);
+        icon.set_pixel_size(ICON_SIZE);
+        let text = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        let title = gtk::Label::new(None);
+        title.add_css_class("systray__tooltip-title");
+        title.set_xalign(0f32);
+        let description = gtk::Label::new(None);
+        description.add_css_class("systray__tooltip-description");
+        description.set_xalign(0f32);
+        description.set_wrap(true);
+        description.set_max_width_chars(50);
+        text.append(&title);
+        text.append(&description);
+        root.append(&icon);
+        root.append(&text);
+        Self {
+            root,
+            icon,
+            title,
+            description,
+        }
+    }
+
+    /// Returns whether there is anything to show
+    pub fn update(&self, tooltip: Option<&Tooltip>) -> bool {
+        let Some(tooltip) = tooltip else {
+            return false;
+        };
+        let description = html_to_pango(&tooltip.description);
+        self.title.set_label(&tooltip.title);
+        self.title.set_visible(!tooltip.title.is_empty());
+        self.description.set_markup(&description);
+        self.description.set_visible(!description.is_empty());
+        !tooltip.title.is_empty() || !description.is_empty()
+    }
+}
+
+/// Tags Pango understands as they are
+const PANGO_TAGS: [&str; 9] = ["b", "i", "u", "s", "tt", "big", "small", "sub", "sup"];
+
+/// Converts the HTML subset allowed in tooltips to Pango markup.
+/// Links are underlined, line breaks and paragraphs become new lines,
+/// other tags are dropped and unclosed ones are closed at the end.
+pub fn html_to_pango(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut open = Vec::<&str>::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        push_text(&mut result, &rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            // Not a tag, just a less-than sign
+            push_text(&mut result, &rest[start..]);
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        if !tag.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/') {
+            push_text(&mut result, "<");
+            rest = &rest[start + 1..];
+            continue;
+        }
+        rest = &rest[start + end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let name = match name.as_str() {
+            "br" => {
+                result.push('\n');
+                continue;
+            }
+            "p" => {
+                if closing {
+                    result.push('\n');
+                }
+                continue;
+            }
+            "a" => "u",
+            "strong" => "b",
+            "em" => "i",
+            name => match PANGO_TAGS.iter().find(|it| **it == name) {
+                Some(name) => *name,
+                None => continue,
+            },
+        };
+        if !closing {
+            result.push_str(&format!("<{name}>"));
+            open.push(name);
+        } else if let Some(index) = open.iter().rposition(|it| *it == name) {
+            // Tags opened inside are closed first to keep the markup valid
+            for name in open.drain(index..).rev() {
+                result.push_str(&format!("</{name}>"));
+            }
+        }
+    }
+    push_text(&mut result, rest);
+    for name in open.into_iter().rev() {
+        result.push_str(&format!("</{name}>"));
+    }
+    result.trim_end().to_string()
+}
+
+/// Escapes text, keeping entities that are already there
+fn push_text(result: &mut String, text: &str) {
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        result.push_str(&glib::markup_escape_text(&rest[..index]));
+        rest = &rest[index..];
+        let entity = rest
+            .find(';')
+            .map(|end| &rest[..=end])
+            .filter(|it| is_entity(it));
+        match entity {
+            Some(entity) => {
+                result.push_str(entity);
+                rest = &rest[entity.len()..];
+            }
+            None => {
+                result.push_str("&amp;");
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(&glib::markup_escape_text(rest));
+}
+
+fn is_entity(entity: &str) -> bool {
+    let name = &entity[1..entity.len() - 1];
+    match name.strip_prefix('#') {
+        Some(code) => match code.strip_prefix(['x', 'X']) {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+        },
+        None => ["amp", "lt", "gt", "quot", "apos"].contains(&name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::html_to_pango;
+
+    #[test]
+    fn test_html_to_pango() {
+        assert_eq!(html_to_pango("Plain text"), "Plain text");
+        assert_eq!(
+            html_to_pango("<b>Bold</b> <I>italic</I>"),
+            "<b>Bold</b> <i>italic</i>"
+        );
+        assert_eq!(html_to_pango("One<br>Two<br/>Three"), "One\nTwo\nThree");
+        assert_eq!(html_to_pango("<p>First</p><p>Second</p>"), "First\nSecond");
+        assert_eq!(
+            html_to_pango("<a href=\"https://example.com\">link</a>"),
+            "<u>link</u>"
+        );
+        assert_eq!(html_to_pango("<img src=\"x.png\"/>Synced"), "Synced");
+        assert_eq!(
+            html_to_pango("Tom & Jerry &amp; co"),
+            "Tom &amp; Jerry &amp; co"
+        );
+        assert_eq!(html_to_pango("1 < 2"), "1 &lt; 2");
+        assert_eq!(html_to_pango("1 < 2 > 0"), "1 &lt; 2 &gt; 0");
+        assert_eq!(html_to_pango("&#169; &copy;"), "&#169; &amp;copy;");
+        assert_eq!(
+            html_to_pango("<b><i>unclosed</b>"),
+            "<b><i>unclosed</i></b>"
+        );
+        assert_eq!(html_to_pango("stray</i> close"), "stray close");
+    }
+}
@@ -33,6 +33,9 @@ pub enum HyprlandMessage {
     ActiveWindow {
         window: Option<WindowEventData>,
     },
+    CloseWindow {
+        address: hyprland::shared::Address,
+    },
     SwitchKeyboardLayout {
         keyboard_name: String,
         layout_name: String,
@@ -0,0 +1,169 @@
+use crate::workers::{occurrences, parse_calendar, CalendarEntry, Occurrence};
+use gtk::glib;
+use log::{debug, error};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use relm4::{ComponentSender, Worker};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Files are re-read after changes settle, vdirsyncer writes many of them at once
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Agenda is rebuilt periodically so the shown range follows current time
+const REFRESH: Duration = Duration::from_secs(15 * 60);
+/// vdir is `calendars/<collection>/<event>.ics`
+const MAX_DEPTH: u32 = 3;
+
+pub struct AgendaWorker {
+    paths: Vec<PathBuf>,
+    days: u32,
+    _watcher: Option<RecommendedWatcher>,
+    reload_pending: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AgendaInit {
+    /// `.ics` files or vdir directories, `~` is expanded
+    pub paths: Vec<String>,
+    /// How many days ahead to show
+    pub days: u32,
+    /// Module gets `meeting-soon` class when an event starts within this many minutes
+    pub soon_minutes: u32,
+}
+
+impl Default for AgendaInit {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            days: 7,
+            soon_minutes: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AgendaMessage {
+    ScheduleReload,
+    Reload,
+}
+
+impl Worker for AgendaWorker {
+    type Init = AgendaInit;
+    type Input = AgendaMessage;
+    type Output = Vec<Occurrence>;
+
+    fn init(init: Self::Init, sender: ComponentSender<Self>) -> Self {
+        let paths = init
+            .paths
+            .iter()
+            .map(|it| expand_home(it))
+            .collect::<Vec<PathBuf>>();
+
+        let watcher = {
+            let sender = sender.clone();
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if event.kind.is_access() => {}
+                Ok(_) => sender.input(AgendaMessage::ScheduleReload),
+                Err(err) => error!("Error watching calendars: {err}"),
+            })
+        };
+        let watcher = match watcher {
+            Ok(mut watcher) => {
+                for path in &paths {
+                    if let Err(err) = watcher.watch(path, RecursiveMode::Recursive) {
+                        error!("Error watching {}: {err}", path.display());
+                    }
+                }
+                Some(watcher)
+            }
+            Err(err) => {
+                error!("Error creating calendar watcher: {err}");
+                None
+            }
+        };
+
+        relm4::spawn(async move {
+            let mut interval = tokio::time::interval(REFRESH);
+            loop {
+                interval.tick().await;
+                sender.input(AgendaMessage::Reload);
+            }
+        });
+
+        Self {
+            paths,
+            days: init.days,
+            _watcher: watcher,
+            reload_pending: false,
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            AgendaMessage::ScheduleReload => {
+                if self.reload_pending {
+                    return;
+                }
+                self.reload_pending = true;
+                relm4::spawn(async move {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    sender.input(AgendaMessage::Reload);
+                });
+            }
+            AgendaMessage::Reload => {
+                self.reload_pending = false;
+                let entries = self
+                    .paths
+                    .iter()
+                    .flat_map(|it| read_entries(it, 0))
+                    .collect::<Vec<CalendarEntry>>();
+                let now = chrono::Utc::now();
+                let from = now - chrono::Duration::days(1);
+                let to = now + chrono::Duration::days(self.days.into());
+                let occurrences = occurrences(&entries, from, to);
+                debug!("Loaded {} calendar events", occurrences.len());
+                if sender.output(occurrences).is_err() {
+                    error!("Failed to send calendar events!");
+                }
+            }
+        }
+    }
+}
+
+fn read_entries(path: &Path, depth: u32) -> Vec<CalendarEntry> {
+    if path.is_file() {
+        if path.extension().is_some_and(|it| it == "ics") {
+            return match File::open(path) {
+                Ok(file) => parse_calendar(BufReader::new(file)),
+                Err(err) => {
+                    error!("Error reading {}: {err}", path.display());
+                    Vec::new()
+                }
+            };
+        }
+        return Vec::new();
+    }
+    if depth >= MAX_DEPTH {
+        return Vec::new();
+    }
+    match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .flatten()
+            .flat_map(|it| read_entries(&it.path(), depth + 1))
+            .collect(),
+        Err(err) => {
+            error!("Error reading {}: {err}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(path) => glib::home_dir().join(path),
+        None => PathBuf::from(path),
+    }
+}
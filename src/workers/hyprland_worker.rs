@@ -5,7 +5,7 @@ use hyprland::data::Workspace;
 use hyprland::event_listener::{
     LayoutEvent, MonitorEventData, WindowEventData, WorkspaceDestroyedEventData,
 };
-use hyprland::shared::{WorkspaceId, WorkspaceType};
+use hyprland::shared::{Address, WorkspaceId, WorkspaceType};
 use log::{debug, error};
 use relm4::{ComponentSender, Worker};
 
@@ -64,6 +64,14 @@ impl HyprlandHandler {
                     Self::on_active_window_changed(&sender, it);
                 });
             }
+            {
+                let sender = sender.clone();
+                listener.add_window_close_handler(move |it| {
+                    if Self::on_window_closed(&sender, it).is_none() {
+                        error!("Failed to send window closed event!");
+                    }
+                });
+            }
             {
                 let sender = sender.clone();
                 listener.add_monitor_removed_handler(move |it| {
@@ -180,6 +188,13 @@ impl HyprlandHandler {
         Some(())
     }
 
+    fn on_window_closed(sender: &ComponentSender<HyprlandHandler>, address: Address) -> Option<()> {
+        debug!("Window closed: {address}");
+        sender.output(HyprlandMessage::CloseWindow { address }).ok()?;
+
+        Some(())
+    }
+
     fn on_keyboard_layout_changed(
         sender: &ComponentSender<HyprlandHandler>,
         event_data: LayoutEvent,
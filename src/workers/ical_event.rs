@@ -0,0 +1,672 @@
+use chrono::{
+    DateTime, Datelike, Days, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
+use ical::parser::ical::component::IcalEvent;
+use ical::property::Property;
+use log::warn;
+use std::io::BufRead;
+
+/// Safety limit for recurrence expansion
+const MAX_PERIODS: u32 = 20_000;
+
+/// Single occurrence of an event, ready to be shown
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub summary: String,
+    pub location: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub all_day: bool,
+}
+
+/// VEVENT with just the parts needed to build the agenda
+#[derive(Debug, Clone)]
+pub struct CalendarEntry {
+    uid: Option<String>,
+    summary: String,
+    location: Option<String>,
+    start: NaiveDateTime,
+    zone: EventZone,
+    all_day: bool,
+    duration: Duration,
+    rule: Option<RecurrenceRule>,
+    /// Excluded and replaced starts are in UTC, they may have their own TZID
+    exdates: Vec<DateTime<Utc>>,
+    recurrence_id: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EventZone {
+    Utc,
+    /// Floating time and dates are in local time
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RecurrenceRule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDateTime>,
+    /// Weekdays with optional ordinal, e.g. `-1FR` is the last friday of month
+    by_day: Vec<(Option<i32>, Weekday)>,
+    /// Decides which weeks are skipped by weekly rules with an interval
+    week_start: Weekday,
+}
+
+/// Parses all events from iCalendar data, skipping broken ones
+pub fn parse_calendar<B: BufRead>(reader: B) -> Vec<CalendarEntry> {
+    let mut entries = Vec::new();
+    for calendar in ical::IcalParser::new(reader) {
+        match calendar {
+            Ok(calendar) => {
+                entries.extend(calendar.events.iter().filter_map(CalendarEntry::parse));
+            }
+            Err(err) => {
+                warn!("Error parsing calendar: {err}");
+            }
+        }
+    }
+    entries
+}
+
+/// Expands entries into occurrences overlapping `from..to`, sorted by start.
+/// Modified instances of recurring events (RECURRENCE-ID) replace the original ones.
+pub fn occurrences(
+    entries: &[CalendarEntry],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<Occurrence> {
+    let overrides = entries
+        .iter()
+        .filter_map(|it| Some((it.uid.as_deref()?, it.recurrence_id?)))
+        .collect::<Vec<(&str, DateTime<Utc>)>>();
+    let overrides = &overrides;
+    let mut result = entries
+        .iter()
+        .flat_map(|entry| {
+            entry
+                .occurrences(from, to)
+                .into_iter()
+                .filter(move |occurrence| {
+                    entry.recurrence_id.is_some()
+                        || entry.rule.is_none()
+                        || !overrides.iter().any(|(uid, id)| {
+                            Some(*uid) == entry.uid.as_deref() && *id == occurrence.start
+                        })
+                })
+        })
+        .collect::<Vec<Occurrence>>();
+    result.sort_by_key(|it| it.start);
+    result
+}
+
+impl CalendarEntry {
+    fn parse(event: &IcalEvent) -> Option<Self> {
+        let property = |name: &str| event.properties.iter().find(|it| it.name == name);
+        let dtstart = property("DTSTART")?;
+        let (start, zone, all_day) = parse_time(dtstart)?;
+        let duration = match (property("DTEND"), property("DURATION")) {
+            (Some(end), _) => {
+                let (end, end_zone, _) = parse_time(end)?;
+                match (end_zone.to_utc(end), zone.to_utc(start)) {
+                    (Some(end), Some(start)) => end - start,
+                    _ => end - start,
+                }
+            }
+            (None, Some(duration)) => parse_duration(duration.value.as_deref()?)?,
+            (None, None) if all_day => Duration::days(1),
+            (None, None) => Duration::zero(),
+        };
+        let rule = property("RRULE")
+            .and_then(|it| it.value.as_deref())
+            .and_then(|it| RecurrenceRule::parse(it, zone));
+        let exdates = event
+            .properties
+            .iter()
+            .filter(|it| it.name == "EXDATE")
+            .flat_map(|it| {
+                let params = it.params.clone();
+                it.value
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter_map(move |value| {
+                        let property = Property {
+                            name: "EXDATE".to_string(),
+                            params: params.clone(),
+                            value: Some(value.to_string()),
+                        };
+                        let (time, zone, _) = parse_time(&property)?;
+                        zone.to_utc(time)
+                    })
+                    .collect::<Vec<DateTime<Utc>>>()
+            })
+            .collect();
+        let text = |name: &str| {
+            property(name)
+                .and_then(|it| it.value.as_deref())
+                .map(unescape)
+                .filter(|it| !it.is_empty())
+        };
+        Some(Self {
+            uid: text("UID"),
+            summary: text("SUMMARY").unwrap_or_default(),
+            location: text("LOCATION"),
+            start,
+            zone,
+            all_day,
+            duration,
+            rule,
+            exdates,
+            recurrence_id: property("RECURRENCE-ID")
+                .and_then(parse_time)
+                .and_then(|(time, zone, _)| zone.to_utc(time)),
+        })
+    }
+
+    fn occurrences(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Occurrence> {
+        let starts = match &self.rule {
+            Some(rule) => rule.expand(self.start, |start| {
+                self.zone.to_utc(start).is_none_or(|it| it < to)
+            }),
+            None => vec![self.start],
+        };
+        starts
+            .into_iter()
+            .filter_map(|start| self.zone.to_utc(start))
+            .filter(|start| !self.exdates.contains(start))
+            .filter_map(|start| {
+                let end = start + self.duration;
+                let overlaps = start < to && (end > from || (end == start && start >= from));
+                overlaps.then(|| Occurrence {
+                    summary: self.summary.clone(),
+                    location: self.location.clone(),
+                    start,
+                    end,
+                    all_day: self.all_day,
+                })
+            })
+            .collect()
+    }
+}
+
+impl EventZone {
+    fn to_utc(self, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            EventZone::Utc => Some(time.and_utc()),
+            EventZone::Local => chrono::Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|it| it.to_utc()),
+            EventZone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|it| it.to_utc()),
+        }
+    }
+}
+
+impl RecurrenceRule {
+    /// Rules with parts that aren't expanded are rejected,
+    /// so they don't produce occurrences on wrong dates
+    fn parse(value: &str, zone: EventZone) -> Option<Self> {
+        let mut rule = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            week_start: Weekday::Mon,
+        };
+        let mut frequency = None;
+        for part in value.split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key {
+                "FREQ" => {
+                    frequency = match value {
+                        "DAILY" => Some(Frequency::Daily),
+                        "WEEKLY" => Some(Frequency::Weekly),
+                        "MONTHLY" => Some(Frequency::Monthly),
+                        "YEARLY" => Some(Frequency::Yearly),
+                        _ => {
+                            warn!("Unsupported recurrence frequency {value}");
+                            None
+                        }
+                    }
+                }
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|it| *it > 0)?,
+                "COUNT" => rule.count = value.parse().ok(),
+                "UNTIL" => {
+                    let (until, until_zone) = parse_date_time(value, zone)?;
+                    // UNTIL is in UTC when start is zoned, compare in start's zone
+                    rule.until = match (until_zone, zone) {
+                        (EventZone::Utc, EventZone::Named(tz)) => {
+                            Some(until.and_utc().with_timezone(&tz).naive_local())
+                        }
+                        (EventZone::Utc, EventZone::Local) => {
+                            Some(until.and_utc().with_timezone(&chrono::Local).naive_local())
+                        }
+                        _ => Some(until),
+                    };
+                }
+                "BYDAY" => {
+                    rule.by_day = value.split(',').map(parse_by_day).collect::<Option<_>>()?;
+                }
+                "WKST" => {
+                    rule.week_start = match parse_by_day(value)? {
+                        (None, day) => day,
+                        _ => return None,
+                    };
+                }
+                _ => {
+                    warn!("Unsupported recurrence rule part {part}");
+                    return None;
+                }
+            }
+        }
+        rule.frequency = frequency?;
+        let by_day_supported = match rule.frequency {
+            Frequency::Weekly => rule.by_day.iter().all(|(ordinal, _)| ordinal.is_none()),
+            Frequency::Monthly => rule.by_day.iter().all(|(ordinal, _)| ordinal.is_some()),
+            Frequency::Daily | Frequency::Yearly => rule.by_day.is_empty(),
+        };
+        if !by_day_supported {
+            warn!("Unsupported BYDAY for {:?} recurrence", rule.frequency);
+            return None;
+        }
+        Some(rule)
+    }
+
+    /// Expands starts from `start` while `before` holds
+    fn expand(
+        &self,
+        start: NaiveDateTime,
+        before: impl Fn(NaiveDateTime) -> bool,
+    ) -> Vec<NaiveDateTime> {
+        let mut result = Vec::new();
+        let mut count = 0;
+        for period in 0..MAX_PERIODS {
+            let Some(candidates) = self.period(start, period) else {
+                break;
+            };
+            for candidate in candidates {
+                if candidate < start {
+                    continue;
+                }
+                if self.until.is_some_and(|until| candidate > until) || !before(candidate) {
+                    return result;
+                }
+                count += 1;
+                if self.count.is_some_and(|it| count > it) {
+                    return result;
+                }
+                result.push(candidate);
+            }
+        }
+        result
+    }
+
+    /// Candidate starts within the n-th period after start, sorted
+    fn period(&self, start: NaiveDateTime, period: u32) -> Option<Vec<NaiveDateTime>> {
+        let step = period.checked_mul(self.interval)?;
+        let date = start.date();
+        let time = start.time();
+        let mut dates = match self.frequency {
+            Frequency::Daily => vec![date.checked_add_days(Days::new(step.into()))?],
+            Frequency::Weekly => {
+                let offset = |day: Weekday| Days::new(day.days_since(self.week_start).into());
+                let week = date
+                    .checked_sub_days(offset(date.weekday()))?
+                    .checked_add_days(Days::new(u64::from(step) * 7))?;
+                if self.by_day.is_empty() {
+                    vec![week.checked_add_days(offset(date.weekday()))?]
+                } else {
+                    self.by_day
+                        .iter()
+                        .filter_map(|(_, day)| week.checked_add_days(offset(*day)))
+                        .collect()
+                }
+            }
+            Frequency::Monthly => {
+                let month = date.with_day(1)?.checked_add_months(Months::new(step))?;
+                let by_ordinal = self
+                    .by_day
+                    .iter()
+                    .filter_map(|(ordinal, day)| Some((ordinal.as_ref()?, day)))
+                    .filter_map(|(ordinal, day)| nth_weekday(month, *ordinal, *day))
+                    .collect::<Vec<NaiveDate>>();
+                if by_ordinal.is_empty() {
+                    month.with_day(date.day()).into_iter().collect()
+                } else {
+                    by_ordinal
+                }
+            }
+            Frequency::Yearly => {
+                let year = date.year().checked_add(step.try_into().ok()?)?;
+                NaiveDate::from_ymd_opt(year, date.month(), date.day())
+                    .into_iter()
+                    .collect()
+            }
+        };
+        dates.sort();
+        Some(dates.into_iter().map(|it| it.and_time(time)).collect())
+    }
+}
+
+fn nth_weekday(month: NaiveDate, ordinal: i32, weekday: Weekday) -> Option<NaiveDate> {
+    let index = u8::try_from(ordinal.unsigned_abs()).ok()?;
+    if ordinal > 0 {
+        return NaiveDate::from_weekday_of_month_opt(month.year(), month.month(), weekday, index);
+    }
+    let last = month.checked_add_months(Months::new(1))?.pred_opt()?;
+    let offset = last.weekday().days_since(weekday) + (u32::from(index) - 1) * 7;
+    let date = last.checked_sub_days(Days::new(offset.into()))?;
+    (date.month() == month.month()).then_some(date)
+}
+
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, day) = value.split_at(split);
+    let day = match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => Some(ordinal.trim_start_matches('+').parse().ok()?),
+    };
+    Some((ordinal, day))
+}
+
+/// Returns time, its zone and whether it is a date without time
+fn parse_time(property: &Property) -> Option<(NaiveDateTime, EventZone, bool)> {
+    let value = property.value.as_deref()?.trim();
+    let param = |name: &str| {
+        property
+            .params
+            .as_ref()?
+            .iter()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, values)| values.first())
+            .map(String::as_str)
+    };
+    if param("VALUE") == Some("DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((date.and_time(NaiveTime::MIN), EventZone::Local, true));
+    }
+    let zone = match param("TZID") {
+        Some(tzid) => match tzid.parse::<chrono_tz::Tz>() {
+            Ok(tz) => EventZone::Named(tz),
+            Err(_) => {
+                warn!("Unknown time zone {tzid}, using local time");
+                EventZone::Local
+            }
+        },
+        None => EventZone::Local,
+    };
+    let (time, zone) = parse_date_time(value, zone)?;
+    Some((time, zone, false))
+}
+
+/// Parses `20241019T100000` or `20241019T100000Z`
+fn parse_date_time(value: &str, zone: EventZone) -> Option<(NaiveDateTime, EventZone)> {
+    if let Some(value) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        return Some((time, EventZone::Utc));
+    }
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((date.and_time(NaiveTime::MIN), zone));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((time, zone))
+}
+
+/// Parses durations like `PT1H30M`, `P1D` or `P2W`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.trim_start_matches('+')),
+    };
+    let value = value.strip_prefix('P')?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for char in value.chars() {
+        match char {
+            'T' => continue,
+            '0'..='9' => number.push(char),
+            unit => {
+                let amount = number.parse::<i64>().ok()?;
+                number.clear();
+                duration += match unit {
+                    'W' => Duration::weeks(amount),
+                    'D' => Duration::days(amount),
+                    'H' => Duration::hours(amount),
+                    'M' => Duration::minutes(amount),
+                    'S' => Duration::seconds(amount),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(if negative { -duration } else { duration })
+}
+
+/// Unescapes TEXT values in one pass, so `\\n` stays a backslash and `n`
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => result.push(' '),
+            Some(char) => result.push(char),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{occurrences, parse_calendar, parse_duration, unescape, EventZone, RecurrenceRule};
+    use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VEVENT
+UID:standup
+SUMMARY:Standup
+DTSTART:20261019T090000Z
+DTEND:20261019T091500Z
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=6
+EXDATE:20261021T090000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:standup
+RECURRENCE-ID:20261023T090000Z
+SUMMARY:Standup (moved)
+DTSTART:20261023T100000Z
+DTEND:20261023T101500Z
+END:VEVENT
+BEGIN:VEVENT
+UID:holiday
+SUMMARY:Holiday\\, finally
+DTSTART;VALUE=DATE:20261020
+END:VEVENT
+BEGIN:VEVENT
+UID:berlin
+SUMMARY:Berlin lunch
+DTSTART;TZID=Europe/Berlin:20261022T120000
+DURATION:PT1H
+END:VEVENT
+END:VCALENDAR
+";
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1DT1S"), Some(Duration::seconds(86401)));
+        assert_eq!(parse_duration("-P2W"), Some(Duration::weeks(-2)));
+        assert_eq!(parse_duration("1H"), None);
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let entries = parse_calendar(CALENDAR.as_bytes());
+        assert_eq!(entries.len(), 4);
+
+        let from = Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2026, 10, 27, 0, 0, 0).unwrap();
+        let result = occurrences(&entries, from, to)
+            .into_iter()
+            .filter(|it| !it.all_day)
+            .map(|it| (it.summary, it.start))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "Standup".to_string(),
+                    Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap()
+                ),
+                (
+                    "Berlin lunch".to_string(),
+                    Utc.with_ymd_and_hms(2026, 10, 22, 10, 0, 0).unwrap()
+                ),
+                (
+                    "Standup (moved)".to_string(),
+                    Utc.with_ymd_and_hms(2026, 10, 23, 10, 0, 0).unwrap()
+                ),
+                (
+                    "Standup".to_string(),
+                    Utc.with_ymd_and_hms(2026, 10, 26, 9, 0, 0).unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_day_event() {
+        let entries = parse_calendar(CALENDAR.as_bytes());
+        let from = Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2026, 10, 23, 0, 0, 0).unwrap();
+        let holiday = occurrences(&entries, from, to)
+            .into_iter()
+            .find(|it| it.all_day)
+            .expect("All day event wasn't found!");
+        assert_eq!(holiday.summary, "Holiday, finally");
+        assert_eq!(holiday.end - holiday.start, Duration::days(1));
+    }
+
+    #[test]
+    fn test_unsupported_rule() {
+        let parse = |value| RecurrenceRule::parse(value, EventZone::Utc);
+        assert!(parse("FREQ=MONTHLY;BYDAY=-1FR").is_some());
+        assert!(parse("FREQ=WEEKLY;BYDAY=MO,WE;WKST=MO").is_some());
+        assert!(parse("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;WKST=SU").is_some());
+        assert_eq!(parse("FREQ=WEEKLY;WKST=1SU"), None);
+        assert_eq!(parse("FREQ=MONTHLY;BYMONTHDAY=15"), None);
+        assert_eq!(parse("FREQ=YEARLY;BYMONTH=3;BYDAY=2SU"), None);
+        assert_eq!(parse("FREQ=MONTHLY;BYDAY=-1MO;BYSETPOS=-1"), None);
+        // Every monday of the month, not the start's day of month
+        assert_eq!(parse("FREQ=MONTHLY;BYDAY=MO"), None);
+    }
+
+    #[test]
+    fn test_week_start() {
+        // Example from RFC 5545, the week start moves the skipped weeks
+        let start = NaiveDate::from_ymd_opt(1997, 8, 5)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let expand = |value| {
+            RecurrenceRule::parse(value, EventZone::Utc)
+                .unwrap()
+                .expand(start, |_| true)
+                .into_iter()
+                .map(|it| it.day())
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(
+            expand("FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=MO"),
+            [5, 10, 19, 24]
+        );
+        assert_eq!(
+            expand("FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=SU"),
+            [5, 17, 19, 31]
+        );
+    }
+
+    #[test]
+    fn test_zoned_exceptions() {
+        // Excluded and moved instances are given in other zones than the start
+        let calendar = "BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VEVENT
+UID:sync
+SUMMARY:Sync
+DTSTART;TZID=Europe/Berlin:20261019T110000
+DURATION:PT30M
+RRULE:FREQ=DAILY;COUNT=3
+EXDATE:20261020T090000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:sync
+RECURRENCE-ID;TZID=America/New_York:20261021T050000
+SUMMARY:Sync (moved)
+DTSTART:20261021T120000Z
+DURATION:PT30M
+END:VEVENT
+END:VCALENDAR
+";
+        let entries = parse_calendar(calendar.as_bytes());
+        let from = Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2026, 10, 22, 0, 0, 0).unwrap();
+        let result = occurrences(&entries, from, to)
+            .into_iter()
+            .map(|it| (it.summary, it.start))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                (
+                    "Sync".to_string(),
+                    Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap()
+                ),
+                (
+                    "Sync (moved)".to_string(),
+                    Utc.with_ymd_and_hms(2026, 10, 21, 12, 0, 0).unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a\\, b\\; c\\nd"), "a, b; c d");
+        assert_eq!(unescape("C:\\\\new"), "C:\\new");
+    }
+}
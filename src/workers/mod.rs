@@ -1,3 +1,9 @@
+mod agenda_worker;
 mod hyprland_worker;
+mod ical_event;
+mod pulse_worker;
 
+pub use agenda_worker::*;
 pub use hyprland_worker::*;
+pub use ical_event::*;
+pub use pulse_worker::*;
@@ -0,0 +1,601 @@
+use libpulse_binding as pulse;
+use log::{debug, error, info};
+use pulse::callbacks::ListResult;
+use pulse::context::introspect::{Introspector, ServerInfo, SinkInfo, SinkInputInfo, SourceInfo};
+use pulse::context::subscribe::{Facility, InterestMaskSet, Operation};
+use pulse::context::{Context, FlagSet, State};
+use pulse::mainloop::threaded::Mainloop;
+use pulse::proplist::properties;
+use pulse::volume::{ChannelVolumes, Volume};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Delay between attempts to reach the sound server
+const RETRY: Duration = Duration::from_secs(5);
+/// How often the connection is checked while no commands come
+const CHECK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    /// Output
+    Sink,
+    /// Input, monitors of sinks are left out
+    Source,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    /// Average of channels, 1.0 is 100%
+    pub volume: f64,
+    pub mute: bool,
+    channels: u8,
+}
+
+/// Application playing through a sink
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub index: u32,
+    /// Application name, falls back to the stream name
+    pub name: String,
+    /// What is playing, like a browser tab title
+    pub media: Option<String>,
+    pub icon_name: Option<String>,
+    pub binary: Option<String>,
+    pub volume: f64,
+    pub mute: bool,
+    channels: u8,
+}
+
+/// Everything the sound server reported, sent whole after every change
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PulseState {
+    pub connected: bool,
+    pub default_sink: Option<String>,
+    pub default_source: Option<String>,
+    pub sinks: Vec<Device>,
+    pub sources: Vec<Device>,
+    pub streams: Vec<Stream>,
+}
+
+#[derive(Debug)]
+pub enum PulseCommand {
+    SetVolume(DeviceKind, String, f64),
+    SetMute(DeviceKind, String, bool),
+    SetDefault(DeviceKind, String),
+    SetStreamVolume(u32, f64),
+    SetStreamMute(u32, bool),
+}
+
+/// Talks to PulseAudio or PipeWire-Pulse on its own thread,
+/// the connection is closed when the handle is dropped
+pub struct PulseHandle {
+    commands: mpsc::Sender<PulseCommand>,
+}
+
+impl PulseHandle {
+    /// Connects to `server`, or to the default one found like other clients do
+    pub fn start<F: Fn(PulseState) + Send + 'static>(server: Option<String>, on_change: F) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("pulse".to_string())
+            .spawn(move || {
+                let on_change: Rc<dyn Fn(PulseState)> = Rc::new(on_change);
+                loop {
+                    match run(server.as_deref(), &receiver, on_change.clone()) {
+                        Ok(()) => return,
+                        Err(err) => {
+                            error!("PulseAudio is unavailable, retrying in {RETRY:?}: {err}");
+                            on_change(PulseState::default());
+                        }
+                    }
+                    std::thread::sleep(RETRY);
+                    // Commands for the old connection don't make sense anymore
+                    while receiver.try_recv().is_ok() {}
+                }
+            });
+        if let Err(err) = thread {
+            error!("Error starting PulseAudio thread: {err}");
+        }
+        Self { commands }
+    }
+
+    pub fn send(&self, command: PulseCommand) {
+        if let Err(err) = self.commands.send(command) {
+            error!("PulseAudio thread is gone: {err}");
+        }
+    }
+}
+
+impl PulseState {
+    pub fn devices(&self, kind: DeviceKind) -> &[Device] {
+        match kind {
+            DeviceKind::Sink => &self.sinks,
+            DeviceKind::Source => &self.sources,
+        }
+    }
+
+    pub fn default_name(&self, kind: DeviceKind) -> Option<&str> {
+        match kind {
+            DeviceKind::Sink => self.default_sink.as_deref(),
+            DeviceKind::Source => self.default_source.as_deref(),
+        }
+    }
+
+    pub fn default_device(&self, kind: DeviceKind) -> Option<&Device> {
+        let name = self.default_name(kind)?;
+        self.devices(kind).iter().find(|it| it.name == name)
+    }
+
+    fn devices_mut(&mut self, kind: DeviceKind) -> &mut Vec<Device> {
+        match kind {
+            DeviceKind::Sink => &mut self.sinks,
+            DeviceKind::Source => &mut self.sources,
+        }
+    }
+
+    fn upsert(&mut self, kind: DeviceKind, device: Device) {
+        let devices = self.devices_mut(kind);
+        match devices.iter_mut().find(|it| it.index == device.index) {
+            Some(it) => *it = device,
+            None => devices.push(device),
+        }
+    }
+
+    fn remove(&mut self, kind: DeviceKind, index: u32) {
+        self.devices_mut(kind).retain(|it| it.index != index);
+    }
+
+    fn upsert_stream(&mut self, stream: Stream) {
+        match self.streams.iter_mut().find(|it| it.index == stream.index) {
+            Some(it) => *it = stream,
+            None => self.streams.push(stream),
+        }
+    }
+}
+
+impl Device {
+    fn from_sink(info: &SinkInfo) -> Option<Self> {
+        Some(Self {
+            index: info.index,
+            name: info.name.as_ref()?.to_string(),
+            description: describe(info.description.as_deref(), info.name.as_deref()),
+            volume: ratio(&info.volume),
+            mute: info.mute,
+            channels: info.volume.len(),
+        })
+    }
+
+    fn from_source(info: &SourceInfo) -> Option<Self> {
+        if info.monitor_of_sink.is_some() {
+            return None;
+        }
+        Some(Self {
+            index: info.index,
+            name: info.name.as_ref()?.to_string(),
+            description: describe(info.description.as_deref(), info.name.as_deref()),
+            volume: ratio(&info.volume),
+            mute: info.mute,
+            channels: info.volume.len(),
+        })
+    }
+}
+
+impl Stream {
+    fn from_sink_input(info: &SinkInputInfo) -> Option<Self> {
+        // Streams without volume, like peak meters, can't be mixed
+        if !info.has_volume {
+            return None;
+        }
+        let property = |name: &str| info.proplist.get_str(name).filter(|it| !it.is_empty());
+        let media = property(properties::MEDIA_NAME)
+            .or_else(|| info.name.as_ref().map(|it| it.to_string()))
+            .filter(|it| !it.is_empty());
+        let name = property(properties::APPLICATION_NAME)
+            .or_else(|| media.clone())
+            .unwrap_or_else(|| format!("Stream {}", info.index));
+        Some(Self {
+            index: info.index,
+            media: media.filter(|it| *it != name),
+            name,
+            icon_name: property(properties::APPLICATION_ICON_NAME),
+            binary: property(properties::APPLICATION_PROCESS_BINARY),
+            volume: ratio(&info.volume),
+            mute: info.mute,
+            channels: info.volume.len(),
+        })
+    }
+}
+
+fn describe(description: Option<&str>, name: Option<&str>) -> String {
+    description.or(name).unwrap_or_default().to_string()
+}
+
+fn ratio(volumes: &ChannelVolumes) -> f64 {
+    volumes.avg().0 as f64 / Volume::NORMAL.0 as f64
+}
+
+fn channel_volumes(channels: u8, ratio: f64) -> ChannelVolumes {
+    let mut volumes = ChannelVolumes::default();
+    let volume = (ratio.max(0f64) * Volume::NORMAL.0 as f64).round() as u32;
+    volumes.set(channels.max(1), Volume(volume));
+    volumes
+}
+
+type Shared<T> = Rc<RefCell<T>>;
+
+/// Serves one connection, returns `Ok` when the handle is dropped
+fn run(
+    server: Option<&str>,
+    receiver: &mpsc::Receiver<PulseCommand>,
+    on_change: Rc<dyn Fn(PulseState)>,
+) -> Result<(), String> {
+    let mainloop = Rc::new(RefCell::new(
+        Mainloop::new().ok_or("Error creating mainloop")?,
+    ));
+    let context = Rc::new(RefCell::new(
+        Context::new(&*mainloop.borrow(), "rgb").ok_or("Error creating context")?,
+    ));
+
+    {
+        let mainloop = mainloop.clone();
+        // Called with the mainloop locked, borrowing it would panic
+        context
+            .borrow_mut()
+            .set_state_callback(Some(Box::new(move || unsafe {
+                (*mainloop.as_ptr()).signal(false);
+            })));
+    }
+    context
+        .borrow_mut()
+        .connect(server, FlagSet::NOFLAGS, None)
+        .map_err(|it| it.to_string())?;
+
+    mainloop.borrow_mut().lock();
+    if let Err(err) = mainloop.borrow_mut().start() {
+        mainloop.borrow_mut().unlock();
+        return Err(err.to_string());
+    }
+    loop {
+        match context.borrow().get_state() {
+            State::Ready => break,
+            State::Failed | State::Terminated => {
+                mainloop.borrow_mut().unlock();
+                mainloop.borrow_mut().stop();
+                return Err("Connection failed".to_string());
+            }
+            _ => mainloop.borrow_mut().wait(),
+        }
+    }
+    context.borrow_mut().set_state_callback(None);
+    info!("Connected to PulseAudio");
+
+    let state = Rc::new(RefCell::new(PulseState {
+        connected: true,
+        ..PulseState::default()
+    }));
+    let introspect = Rc::new(RefCell::new(context.borrow().introspect()));
+    {
+        let introspect_ref = introspect.borrow();
+        introspect_ref.get_server_info(on_server(&state, &on_change));
+        introspect_ref.get_sink_info_list(on_sink(&state, &on_change));
+        introspect_ref.get_source_info_list(on_source(&state, &on_change));
+        introspect_ref.get_sink_input_info_list(on_sink_input(&state, &on_change));
+    }
+    {
+        let state = state.clone();
+        let on_change = on_change.clone();
+        let introspect = introspect.clone();
+        context.borrow_mut().set_subscribe_callback(Some(Box::new(
+            move |facility, operation, index| {
+                on_event(&introspect, &state, &on_change, facility, operation, index)
+            },
+        )));
+    }
+    context.borrow_mut().subscribe(
+        InterestMaskSet::SINK
+            | InterestMaskSet::SOURCE
+            | InterestMaskSet::SINK_INPUT
+            | InterestMaskSet::SERVER,
+        |success| {
+            if !success {
+                error!("Error subscribing to PulseAudio events");
+            }
+        },
+    );
+    mainloop.borrow_mut().unlock();
+
+    let result = loop {
+        let command = match receiver.recv_timeout(CHECK) {
+            Ok(command) => Some(command),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break Ok(()),
+        };
+        mainloop.borrow_mut().lock();
+        if let Some(command) = command {
+            debug!("PulseAudio command {command:?}");
+            run_command(&context, &introspect, &state.borrow(), command);
+        }
+        let good = context.borrow().get_state().is_good();
+        mainloop.borrow_mut().unlock();
+        if !good {
+            break Err("Connection lost".to_string());
+        }
+    };
+
+    mainloop.borrow_mut().lock();
+    context.borrow_mut().disconnect();
+    mainloop.borrow_mut().unlock();
+    mainloop.borrow_mut().stop();
+    result
+}
+
+fn run_command(
+    context: &Shared<Context>,
+    introspect: &Shared<Introspector>,
+    state: &PulseState,
+    command: PulseCommand,
+) {
+    let mut introspect = introspect.borrow_mut();
+    match command {
+        PulseCommand::SetVolume(kind, name, volume) => {
+            let channels = state
+                .devices(kind)
+                .iter()
+                .find(|it| it.name == name)
+                .map(|it| it.channels)
+                .unwrap_or(2);
+            let volumes = channel_volumes(channels, volume);
+            match kind {
+                DeviceKind::Sink => introspect.set_sink_volume_by_name(&name, &volumes, None),
+                DeviceKind::Source => introspect.set_source_volume_by_name(&name, &volumes, None),
+            };
+        }
+        PulseCommand::SetMute(kind, name, mute) => {
+            match kind {
+                DeviceKind::Sink => introspect.set_sink_mute_by_name(&name, mute, None),
+                DeviceKind::Source => introspect.set_source_mute_by_name(&name, mute, None),
+            };
+        }
+        PulseCommand::SetDefault(kind, name) => {
+            let mut context = context.borrow_mut();
+            let callback = |success| {
+                if !success {
+                    error!("Error changing default device");
+                }
+            };
+            match kind {
+                DeviceKind::Sink => context.set_default_sink(&name, callback),
+                DeviceKind::Source => context.set_default_source(&name, callback),
+            };
+        }
+        PulseCommand::SetStreamVolume(index, volume) => {
+            let channels = state
+                .streams
+                .iter()
+                .find(|it| it.index == index)
+                .map(|it| it.channels)
+                .unwrap_or(2);
+            introspect.set_sink_input_volume(index, &channel_volumes(channels, volume), None);
+        }
+        PulseCommand::SetStreamMute(index, mute) => {
+            introspect.set_sink_input_mute(index, mute, None);
+        }
+    }
+}
+
+fn on_event(
+    introspect: &Shared<Introspector>,
+    state: &Shared<PulseState>,
+    on_change: &Rc<dyn Fn(PulseState)>,
+    facility: Option<Facility>,
+    operation: Option<Operation>,
+    index: u32,
+) {
+    let introspect = introspect.borrow();
+    let kind = match facility {
+        Some(Facility::Sink) => DeviceKind::Sink,
+        Some(Facility::Source) => DeviceKind::Source,
+        Some(Facility::Server) => {
+            introspect.get_server_info(on_server(state, on_change));
+            return;
+        }
+        Some(Facility::SinkInput) => {
+            if operation == Some(Operation::Removed) {
+                state.borrow_mut().streams.retain(|it| it.index != index);
+                on_change(state.borrow().clone());
+            } else {
+                introspect.get_sink_input_info(index, on_sink_input(state, on_change));
+            }
+            return;
+        }
+        _ => return,
+    };
+    if operation == Some(Operation::Removed) {
+        state.borrow_mut().remove(kind, index);
+        on_change(state.borrow().clone());
+        return;
+    }
+    match kind {
+        DeviceKind::Sink => {
+            introspect.get_sink_info_by_index(index, on_sink(state, on_change));
+        }
+        DeviceKind::Source => {
+            introspect.get_source_info_by_index(index, on_source(state, on_change));
+        }
+    }
+}
+
+fn on_server(
+    state: &Shared<PulseState>,
+    on_change: &Rc<dyn Fn(PulseState)>,
+) -> impl FnMut(&ServerInfo) + 'static {
+    let (state, on_change) = (state.clone(), on_change.clone());
+    move |info| {
+        let mut state_ref = state.borrow_mut();
+        state_ref.default_sink = info.default_sink_name.as_ref().map(|it| it.to_string());
+        state_ref.default_source = info.default_source_name.as_ref().map(|it| it.to_string());
+        on_change(state_ref.clone());
+    }
+}
+
+fn on_sink(
+    state: &Shared<PulseState>,
+    on_change: &Rc<dyn Fn(PulseState)>,
+) -> impl FnMut(ListResult<&SinkInfo>) + 'static {
+    let (state, on_change) = (state.clone(), on_change.clone());
+    move |result| match result {
+        ListResult::Item(info) => {
+            if let Some(device) = Device::from_sink(info) {
+                state.borrow_mut().upsert(DeviceKind::Sink, device);
+            }
+        }
+        ListResult::End => on_change(state.borrow().clone()),
+        ListResult::Error => error!("Error reading PulseAudio sinks"),
+    }
+}
+
+fn on_source(
+    state: &Shared<PulseState>,
+    on_change: &Rc<dyn Fn(PulseState)>,
+) -> impl FnMut(ListResult<&SourceInfo>) + 'static {
+    let (state, on_change) = (state.clone(), on_change.clone());
+    move |result| match result {
+        ListResult::Item(info) => {
+            if let Some(device) = Device::from_source(info) {
+                state.borrow_mut().upsert(DeviceKind::Source, device);
+            }
+        }
+        ListResult::End => on_change(state.borrow().clone()),
+        ListResult::Error => error!("Error reading PulseAudio sources"),
+    }
+}
+
+fn on_sink_input(
+    state: &Shared<PulseState>,
+    on_change: &Rc<dyn Fn(PulseState)>,
+) -> impl FnMut(ListResult<&SinkInputInfo>) + 'static {
+    let (state, on_change) = (state.clone(), on_change.clone());
+    move |result| match result {
+        ListResult::Item(info) => {
+            let mut state = state.borrow_mut();
+            match Stream::from_sink_input(info) {
+                Some(stream) => state.upsert_stream(stream),
+                None => state.streams.retain(|it| it.index != info.index),
+            }
+        }
+        ListResult::End => on_change(state.borrow().clone()),
+        ListResult::Error => error!("Error reading PulseAudio streams"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceKind, PulseCommand, PulseHandle, PulseState};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    /// Waits for a state matching the check
+    fn wait_for(
+        receiver: &mpsc::Receiver<PulseState>,
+        check: impl Fn(&PulseState) -> bool,
+    ) -> PulseState {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let state = receiver.recv_timeout(timeout).expect("No matching state");
+            if check(&state) {
+                return state;
+            }
+        }
+    }
+
+    /// Runs a private PulseAudio server with a null sink, skipped when it can't be started
+    #[test]
+    fn test_null_sink() {
+        let dir = std::env::temp_dir().join(format!("rgb-pulse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("native");
+        let server = Command::new("pulseaudio")
+            .args([
+                "-n",
+                "--daemonize=no",
+                "--exit-idle-time=-1",
+                "--use-pid-file=no",
+            ])
+            .arg(format!(
+                "--load=module-native-protocol-unix auth-anonymous=1 socket={}",
+                socket.display()
+            ))
+            .arg("--load=module-null-sink sink_name=rgb_test")
+            .env("XDG_RUNTIME_DIR", &dir)
+            .env("HOME", &dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut server = match server {
+            Ok(server) => server,
+            Err(err) => {
+                eprintln!("Skipping, error starting pulseaudio: {err}");
+                _ = std::fs::remove_dir_all(&dir);
+                return;
+            }
+        };
+        // E.g. when running as root or without a usable runtime dir it exits right away
+        let started = Instant::now();
+        while !socket.exists()
+            && started.elapsed() < Duration::from_secs(10)
+            && server.try_wait().is_ok_and(|it| it.is_none())
+        {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if !socket.exists() {
+            eprintln!("Skipping, pulseaudio server didn't start");
+            _ = server.kill();
+            _ = server.wait();
+            _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let address = format!("unix:{}", socket.display());
+        let handle = PulseHandle::start(Some(address), move |state| {
+            _ = sender.send(state);
+        });
+        let state = wait_for(&receiver, |it| {
+            it.default_device(DeviceKind::Sink)
+                .is_some_and(|it| it.name == "rgb_test")
+        });
+        let sink = state.default_device(DeviceKind::Sink).unwrap();
+        assert!(!sink.mute);
+        assert!(state
+            .sources
+            .iter()
+            .all(|it| !it.name.ends_with(".monitor")));
+
+        handle.send(PulseCommand::SetVolume(
+            DeviceKind::Sink,
+            "rgb_test".to_string(),
+            0.5,
+        ));
+        handle.send(PulseCommand::SetMute(
+            DeviceKind::Sink,
+            "rgb_test".to_string(),
+            true,
+        ));
+        wait_for(&receiver, |it| {
+            it.default_device(DeviceKind::Sink)
+                .is_some_and(|it| it.mute && (it.volume - 0.5).abs() < 0.01)
+        });
+
+        drop(handle);
+        server.kill().unwrap();
+        _ = server.wait();
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}